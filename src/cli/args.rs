@@ -0,0 +1,213 @@
+use super::SavesCommand;
+use crate::models::{
+    CheckpointOptions, ConcurrencyOptions, ConflictPolicy, CopyMethod, CopyOptions, ErrorPolicy,
+    Filters, Mode, PreserveOptions, RetryOptions, RunSettings, Strategy,
+};
+use clap::{Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Parser, Debug)]
+#[command(name = "file-organizer", version, about)]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// How to lay out the output folder
+    #[arg(long, value_enum, default_value_t = Strategy::TypeDate)]
+    pub strategy: Strategy,
+
+    /// What to do when a target file already exists
+    #[arg(long, value_enum, default_value_t = ConflictPolicy::Overwrite)]
+    pub conflict: ConflictPolicy,
+
+    /// Copy files or move them out of the input folder
+    #[arg(long, value_enum, default_value_t = Mode::Copy)]
+    pub mode: Mode,
+
+    /// Only organize files with these extensions (comma separated)
+    #[arg(long, value_delimiter = ',')]
+    pub ext: Vec<String>,
+
+    /// Skip files smaller than this many bytes
+    #[arg(long, value_name = "BYTES")]
+    pub min_size: Option<u64>,
+
+    /// Skip files larger than this many bytes
+    #[arg(long, value_name = "BYTES")]
+    pub max_size: Option<u64>,
+
+    /// Flush each copied file to disk before moving it into place
+    #[arg(long)]
+    pub fsync: bool,
+
+    /// Hash copied files and check the hashes again when resuming
+    #[arg(long)]
+    pub hash: bool,
+
+    /// Copy files again even if an earlier run organized them and they haven't changed, without updating the index either
+    #[arg(long)]
+    pub no_index: bool,
+
+    /// Metadata to leave behind when copying (comma separated)
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub no_preserve: Vec<Preserve>,
+
+    /// Write the save file after this many copied files
+    #[arg(long, value_name = "FILES", default_value_t = 100)]
+    pub checkpoint_every: usize,
+
+    /// Write the save file at least this often while copying
+    #[arg(long, value_name = "SECONDS", default_value_t = 30)]
+    pub checkpoint_secs: u64,
+
+    /// What to do when a file can't be copied
+    #[arg(long, value_enum, default_value_t = ErrorPolicy::Abort)]
+    pub on_error: ErrorPolicy,
+
+    /// How many more times to try a failing file with --on-error retry
+    #[arg(long, value_name = "COUNT", default_value_t = 3)]
+    pub retries: u32,
+
+    /// How often to retry timeouts and other network file system hiccups
+    #[arg(long, value_name = "COUNT", default_value_t = 5)]
+    pub io_retries: u32,
+
+    /// How many files to copy at the same time
+    #[arg(long, value_name = "COUNT", default_value_t = 4)]
+    pub jobs: usize,
+
+    /// How many files to copy at the same time from or to one disk [default: 1 for spinning disks, --jobs for others]
+    #[arg(long, value_name = "COUNT")]
+    pub device_jobs: Option<usize>,
+
+    /// How many folders to read at the same time while scanning the input
+    #[arg(long, value_name = "COUNT", default_value_t = 8)]
+    pub scan_threads: usize,
+
+    /// Leave files modified in the last this many seconds for a later run, they may still be downloading
+    #[arg(long, value_name = "SECS", default_value_t = 5)]
+    pub quiet_period: u64,
+
+    /// How to copy file contents, see `bench` to compare them
+    #[arg(long, value_enum, default_value_t = CopyMethod::Auto)]
+    pub copy_method: CopyMethod,
+
+    /// How to show progress
+    #[arg(long, value_enum, default_value_t = Output::Tui)]
+    pub output: Output,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Inspect save files
+    Saves {
+        #[command(subcommand)]
+        command: SavesCommand,
+    },
+
+    /// Reverse what a run copied, moved and created, or list runs
+    Undo {
+        /// Run name as listed by `undo`, or the path to its log
+        run: Option<String>,
+
+        /// Don't ask for confirmation
+        #[arg(long, short)]
+        yes: bool,
+    },
+
+    /// Time each copy method between two folders, to pick one for --copy-method
+    Bench {
+        /// Folder on the disk to copy from
+        from: PathBuf,
+
+        /// Folder on the disk to copy to
+        to: PathBuf,
+
+        /// Size of the test file in MB
+        #[arg(long, value_name = "MB", default_value_t = 256)]
+        size: u64,
+    },
+
+    /// Organize files as they arrive in a folder until stopped. Settings like --strategy go before `watch`
+    Watch {
+        /// Folder to watch
+        source: PathBuf,
+
+        /// Folder to organize into
+        destination: PathBuf,
+
+        /// Seconds a file has to stay unchanged before it is copied
+        #[arg(long, value_name = "SECS", default_value_t = 5)]
+        settle: u64,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Output {
+    // The interactive progress screen
+    Tui,
+    // A line per finished file
+    Text,
+    // A JSON object per event
+    Json,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Preserve {
+    Times,
+    Permissions,
+    Ownership,
+    Xattrs,
+}
+
+impl Args {
+    pub fn run_settings(&self) -> RunSettings {
+        RunSettings {
+            strategy: self.strategy,
+            filters: Filters {
+                extensions: self
+                    .ext
+                    .iter()
+                    .map(|e| e.trim_start_matches('.').to_lowercase())
+                    .collect(),
+                min_size: self.min_size,
+                max_size: self.max_size,
+            },
+            conflict_policy: self.conflict,
+            mode: self.mode,
+        }
+    }
+
+    pub fn copy_options(&self) -> CopyOptions {
+        let keeps = |what| !self.no_preserve.contains(&what);
+
+        CopyOptions {
+            fsync: self.fsync,
+            hash: self.hash,
+            preserve: PreserveOptions {
+                times: keeps(Preserve::Times),
+                permissions: keeps(Preserve::Permissions),
+                ownership: keeps(Preserve::Ownership),
+                xattrs: keeps(Preserve::Xattrs),
+            },
+            checkpoint: CheckpointOptions {
+                every_files: self.checkpoint_every.max(1),
+                interval: Duration::from_secs(self.checkpoint_secs),
+            },
+            on_error: self.on_error,
+            retries: self.retries,
+            retry: RetryOptions {
+                limit: self.io_retries,
+                ..RetryOptions::default()
+            },
+            concurrency: ConcurrencyOptions {
+                jobs: self.jobs.max(1),
+                per_device: self.device_jobs.map(|jobs| jobs.max(1)),
+                scan_threads: self.scan_threads.max(1),
+            },
+            method: self.copy_method,
+            quiet_period: Duration::from_secs(self.quiet_period),
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use crate::{
+    OrganizeError,
+    models::{CopyMethod, FileOperation},
+    organizer::copy_file_with,
+};
+use clap::ValueEnum;
+use colored::*;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+const MB: u64 = 1024 * 1024;
+
+pub fn run_bench(from: PathBuf, to: PathBuf, size: u64) -> Result<(), OrganizeError> {
+    let size = size.max(1);
+    let source = from.join(format!(".forg-bench-{}", std::process::id()));
+
+    println!(
+        "\n{} {} MB from {} to {}",
+        "⏱  Copying".bright_cyan(),
+        size,
+        from.display(),
+        to.display()
+    );
+    write_test_file(&source, size * MB).map_err(|error| OrganizeError::File {
+        operation: FileOperation::WriteTarget,
+        source: source.clone(),
+        target: source.clone(),
+        error,
+    })?;
+
+    let result = bench_methods(&source, &to);
+    let _ = fs::remove_file(&source);
+    result
+}
+
+fn bench_methods(source: &Path, to: &Path) -> Result<(), OrganizeError> {
+    // Read once up front so every method finds the source equally cached
+    io::copy(
+        &mut File::open(source).map_err(|error| read_error(source, error))?,
+        &mut io::sink(),
+    )
+    .map_err(|error| read_error(source, error))?;
+
+    for method in [
+        CopyMethod::Buffered,
+        CopyMethod::Sendfile,
+        CopyMethod::CopyFileRange,
+    ] {
+        let name = method
+            .to_possible_value()
+            .map(|value| value.get_name().to_string())
+            .unwrap_or_default();
+        let target = to.join(format!(".forg-bench-{}-{}", std::process::id(), name));
+
+        let started = Instant::now();
+        let result = copy_file_with(method, source, &target);
+        let elapsed = started.elapsed();
+        let _ = fs::remove_file(&target);
+
+        match result {
+            Ok(bytes) => println!(
+                "  {:<16} {:>8.1} MB/s",
+                name.bright_green(),
+                bytes as f64 / MB as f64 / elapsed.as_secs_f64().max(f64::EPSILON)
+            ),
+            Err(e) if e.kind() == io::ErrorKind::Unsupported => {
+                println!("  {:<16} {}", name.yellow(), "unsupported here".dimmed())
+            }
+            Err(e) => println!("  {:<16} {}", name.red(), e.to_string().red()),
+        }
+    }
+
+    println!(
+        "\nPass the fastest to {}, or leave it on auto",
+        "--copy-method".bright_cyan()
+    );
+    Ok(())
+}
+
+// Random-looking bytes, so compressing or deduplicating file systems can't
+// make one method look faster than the disk is
+fn write_test_file(path: &Path, len: u64) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    let mut state = 0x9e37_79b9_7f4a_7c15_u64 ^ u64::from(std::process::id());
+    let mut block = vec![0; MB as usize];
+    let mut left = len;
+    while left > 0 {
+        for word in block.chunks_exact_mut(8) {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            word.copy_from_slice(&state.to_le_bytes());
+        }
+        let step = left.min(block.len() as u64) as usize;
+        file.write_all(&block[..step])?;
+        left -= step as u64;
+    }
+    file.sync_all()
+}
+
+fn read_error(path: &Path, error: io::Error) -> OrganizeError {
+    OrganizeError::File {
+        operation: FileOperation::ReadSource,
+        source: path.to_path_buf(),
+        target: path.to_path_buf(),
+        error,
+    }
+}
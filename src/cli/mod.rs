@@ -1,17 +1,31 @@
+use crate::say;
 use colored::*;
+use std::process::ExitCode;
 
+mod args;
+mod bench;
 mod operation;
+mod saves;
+mod undo;
+mod watch;
+pub use args::{Args, Command, Output};
+pub use bench::run_bench;
+pub use saves::{SavesCommand, run_saves_command};
 pub use operation::select_operation_mode;
+pub use undo::run_undo;
+pub use watch::run_watch;
 
 pub fn print_header() {
-    println!("{}", "\n🚀 File Organizer v1.0".bright_blue().bold());
-    println!("{}", "===================".bright_blue());
+    say!("{}", "\n🚀 File Organizer v1.0".bright_blue().bold());
+    say!("{}", "===================".bright_blue());
 }
 
-pub fn handle_error(error: impl std::fmt::Display, output_path: Option<&std::path::PathBuf>) {
-    if let Some(path) = output_path {
-        crate::ui::cleanup(path);
+// `run_id` names a run whose output should be offered for cleanup. Returns
+// the code the process should exit with.
+pub fn handle_error(error: crate::OrganizeError, run_id: Option<&str>) -> ExitCode {
+    eprintln!("{} {}", "❌ Error:".red(), error.with_causes().bright_red());
+    if let Some(run_id) = run_id {
+        crate::ui::cleanup(run_id);
     }
-    eprintln!("{} {}", "❌ Error:".red(), error.to_string().bright_red());
-    std::process::exit(1);
+    ExitCode::from(error.exit_code() as u8)
 }
\ No newline at end of file
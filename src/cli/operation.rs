@@ -1,10 +1,73 @@
-use colored::*;
-use dialoguer::{Select, theme::ColorfulTheme};
-use std::path::PathBuf;
 use crate::models::SaveState;
+use crate::organizer::temp_path_for;
+use crate::say;
+use crate::ui::progress::format_size;
+use crate::vfs::RealFs;
+use chrono::{DateTime, Local};
+use colored::*;
+use dialoguer::{Confirm, Input, Select, theme::ColorfulTheme};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+struct SaveEntry {
+    path: PathBuf,
+    state: Option<SaveState>,
+}
+
+impl SaveEntry {
+    // A save whose input folder is gone can't be resumed, only cleaned up
+    fn is_stale(&self) -> bool {
+        self.state.as_ref().is_none_or(|s| !s.input_path.is_dir())
+    }
+
+    fn label(&self) -> String {
+        let name = self
+            .path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+
+        let Some(state) = &self.state else {
+            return format!("{} {}", name, "(unreadable)".red());
+        };
+
+        let saved_at = state
+            .saved_at
+            .map(|t| {
+                DateTime::<Local>::from(t)
+                    .format("%Y-%m-%d %H:%M")
+                    .to_string()
+            })
+            .unwrap_or_else(|| "unknown time".to_string());
+        let progress = match state.remaining() {
+            Some((files, bytes)) => format!(
+                "{} done, {} left ({})",
+                state.processed_files.len(),
+                files,
+                format_size(bytes)
+            ),
+            None => format!("{} done", state.processed_files.len()),
+        };
+
+        let mut label = format!(
+            "{} | {} → {} | {} | {}",
+            name,
+            state.input_path.display(),
+            state.output_path.display(),
+            saved_at,
+            progress
+        );
+        if self.is_stale() {
+            label.push_str(&format!(" {}", "(input folder missing)".yellow()));
+        }
+        label
+    }
+}
 
 pub fn select_operation_mode() -> Option<PathBuf> {
-    println!("\n{}", "Select operation mode:".bright_cyan());
+    say!("\n{}", "Select operation mode:".bright_cyan());
 
     let options = vec!["Start new organization", "Resume from saved file"];
     let selection = Select::with_theme(&ColorfulTheme::default())
@@ -13,35 +76,145 @@ pub fn select_operation_mode() -> Option<PathBuf> {
         .interact()
         .unwrap_or(0);
 
-    if selection == 1 {
-        match SaveState::list_saves() {
-            Ok(saves) if !saves.is_empty() => {
-                let save_options: Vec<String> = saves
-                    .iter()
-                    .map(|path| {
-                        path.file_name()
-                            .unwrap_or_default()
-                            .to_string_lossy()
-                            .to_string()
-                    })
-                    .collect();
-
-                let save_selection = Select::with_theme(&ColorfulTheme::default())
-                    .with_prompt("Select a save file:")
-                    .items(&save_options)
-                    .default(0)
-                    .interact()
-                    .ok()
-                    .map(|i| saves[i].clone());
-
-                save_selection
-            }
-            _ => {
-                println!("{}", "No save files found.".yellow());
-                None
-            }
+    if selection == 1 { select_save() } else { None }
+}
+
+fn load_saves() -> Vec<SaveEntry> {
+    let mut saves: Vec<SaveEntry> = SaveState::list_saves(&RealFs)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|path| SaveEntry {
+            state: SaveState::load(&RealFs, &path).ok(),
+            path,
+        })
+        .collect();
+
+    // Newest first
+    saves.sort_by_key(|s| std::cmp::Reverse(s.state.as_ref().and_then(|s| s.saved_at)));
+    saves
+}
+
+// Loops until the user resumes a save or backs out to a new run
+fn select_save() -> Option<PathBuf> {
+    loop {
+        let saves = load_saves();
+        if saves.is_empty() {
+            say!("{}", "No save files found.".yellow());
+            return None;
         }
-    } else {
-        None
+
+        let mut items: Vec<String> = saves.iter().map(SaveEntry::label).collect();
+        items.push("Start a new organization instead".to_string());
+
+        let index = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("Select a save file:")
+            .items(&items)
+            .default(0)
+            .interact()
+            .ok()?;
+        let entry = saves.get(index)?;
+
+        if let Some(path) = manage_save(entry) {
+            return Some(path);
+        }
+    }
+}
+
+// Returns the save to resume, or None to go back to the list
+fn manage_save(entry: &SaveEntry) -> Option<PathBuf> {
+    let mut actions = Vec::new();
+    if !entry.is_stale() {
+        actions.push("Resume");
+    }
+    actions.extend(["Rename", "Export", "Delete", "Back"]);
+
+    let action = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("What do you want to do with this save?")
+        .items(&actions)
+        .default(0)
+        .interact()
+        .ok()?;
+
+    let result = match actions[action] {
+        "Resume" => return Some(entry.path.clone()),
+        "Rename" => rename_save(&entry.path),
+        "Export" => export_save(&entry.path),
+        "Delete" => delete_save(entry),
+        _ => Ok(()),
+    };
+
+    if let Err(e) = result {
+        say!("{} {}", "❌ Error:".red(), e);
+    }
+    None
+}
+
+fn rename_save(path: &Path) -> io::Result<()> {
+    let name: String = Input::with_theme(&ColorfulTheme::default())
+        .with_prompt("New name")
+        .interact_text()
+        .map_err(io::Error::other)?;
+
+    let name = name.trim();
+    if name.is_empty() || name.contains(['/', '\\']) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Save names can't be empty or contain path separators",
+        ));
+    }
+
+    let mut new_path = path.with_file_name(name);
+    if new_path.extension().and_then(|e| e.to_str()) != Some("forg") {
+        new_path = path.with_file_name(format!("{}.forg", name));
+    }
+    if new_path.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", new_path.display()),
+        ));
+    }
+
+    fs::rename(path, &new_path)?;
+    say!("{} {}", "✅ Renamed to".green(), new_path.display());
+    Ok(())
+}
+
+fn export_save(path: &Path) -> io::Result<()> {
+    let destination: String = Input::with_theme(&ColorfulTheme::default())
+        .with_prompt("Export to (file or folder)")
+        .interact_text()
+        .map_err(io::Error::other)?;
+
+    let mut destination = PathBuf::from(destination.trim());
+    if destination.is_dir() {
+        destination = destination.join(path.file_name().unwrap_or_default());
     }
-}
\ No newline at end of file
+
+    fs::copy(path, &destination)?;
+    say!("{} {}", "✅ Exported to".green(), destination.display());
+    Ok(())
+}
+
+fn delete_save(entry: &SaveEntry) -> io::Result<()> {
+    let confirmed = Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!(
+            "Delete {}? This can't be undone",
+            entry.path.display()
+        ))
+        .default(false)
+        .interact()
+        .map_err(io::Error::other)?;
+    if !confirmed {
+        return Ok(());
+    }
+
+    fs::remove_file(&entry.path)?;
+
+    // The partial copies are useless without the save that points at them
+    for partial in entry.state.iter().flat_map(|s| &s.in_flight) {
+        let _ = fs::remove_file(temp_path_for(&partial.target));
+    }
+
+    say!("{}", "🗑️  Save deleted".green());
+    Ok(())
+}
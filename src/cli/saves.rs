@@ -0,0 +1,251 @@
+use crate::{
+    OrganizeError, models::SaveState, organizer::temp_path_for, save::format::CURRENT_VERSION,
+    ui::progress::format_size, utils::get_save_dir, vfs::RealFs,
+};
+use chrono::{DateTime, Local};
+use clap::Subcommand;
+use colored::*;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+#[derive(Subcommand, Debug)]
+pub enum SavesCommand {
+    /// Check a save file and print what it contains
+    Show {
+        /// Path to a .forg file, or its name inside the saves folder
+        save: PathBuf,
+
+        /// List every processed file
+        #[arg(long)]
+        files: bool,
+    },
+
+    /// Write a save out as a single JSON document
+    Export {
+        /// Path to a .forg file, or its name inside the saves folder
+        save: PathBuf,
+
+        /// Where to write the JSON file
+        output: PathBuf,
+    },
+
+    /// Turn a JSON save into a resumable save in the saves folder
+    Import {
+        /// JSON save file, as written by `saves export` or older versions
+        file: PathBuf,
+    },
+}
+
+pub fn run_saves_command(command: SavesCommand) -> Result<(), OrganizeError> {
+    match command {
+        SavesCommand::Show { save, files } => show_save(&resolve_save_path(save), files),
+        SavesCommand::Export { save, output } => export_save(&resolve_save_path(save), &output),
+        SavesCommand::Import { file } => import_save(&file),
+    }
+}
+
+fn load_save(path: &Path) -> Result<SaveState, OrganizeError> {
+    SaveState::load(&RealFs, path).map_err(|error| OrganizeError::SaveFile {
+        path: path.to_path_buf(),
+        error,
+    })
+}
+
+fn export_save(path: &Path, output: &Path) -> Result<(), OrganizeError> {
+    load_save(path)?
+        .export_json(&RealFs, output)
+        .map_err(|error| OrganizeError::SaveFile {
+            path: output.to_path_buf(),
+            error,
+        })?;
+
+    println!("{} {}", "✅ Save exported to".green(), output.display());
+    Ok(())
+}
+
+fn import_save(file: &Path) -> Result<(), OrganizeError> {
+    let mut state = load_save(file)?;
+    let save_path = SaveState::new_save_path(&RealFs, &state.input_path).map_err(|error| {
+        OrganizeError::SaveFile {
+            path: get_save_dir(),
+            error,
+        }
+    })?;
+    state
+        .compact_to(&RealFs, &save_path)
+        .map_err(|error| OrganizeError::SaveFile {
+            path: save_path.clone(),
+            error,
+        })?;
+
+    println!("{} {}", "✅ Save imported as".green(), save_path.display());
+    Ok(())
+}
+
+// Bare names are looked up in the saves folder
+fn resolve_save_path(save: PathBuf) -> PathBuf {
+    if save.exists() {
+        return save;
+    }
+    let in_save_dir = get_save_dir().join(&save);
+    if in_save_dir.exists() {
+        in_save_dir
+    } else {
+        save
+    }
+}
+
+fn show_save(path: &Path, list_files: bool) -> Result<(), OrganizeError> {
+    let decoded =
+        SaveState::load_versioned(&RealFs, path).map_err(|error| OrganizeError::SaveFile {
+            path: path.to_path_buf(),
+            error,
+        })?;
+    let state = &decoded.state;
+
+    println!("\n{} {}", "📦 Save file:".bright_cyan(), path.display());
+
+    let version = if decoded.version < CURRENT_VERSION {
+        format!("{} (upgraded from {})", CURRENT_VERSION, decoded.version)
+    } else {
+        CURRENT_VERSION.to_string()
+    };
+    println!("{} {}", "Format version:".green(), version);
+
+    if let Some(saved_at) = state.saved_at {
+        let saved_at: DateTime<Local> = saved_at.into();
+        println!(
+            "{} {}",
+            "Saved at:".green(),
+            saved_at.format("%Y-%m-%d %H:%M:%S")
+        );
+    }
+
+    println!("{} {}", "Input folder:".green(), state.input_path.display());
+    println!(
+        "{} {}",
+        "Output folder:".green(),
+        state.output_path.display()
+    );
+
+    let settings = &state.settings;
+    println!(
+        "{} {:?} layout, {:?} mode, {:?} on conflict",
+        "Settings:".green(),
+        settings.strategy,
+        settings.mode,
+        settings.conflict_policy
+    );
+    if !settings.filters.extensions.is_empty() {
+        println!(
+            "{} {}",
+            "Extensions:".green(),
+            settings.filters.extensions.join(", ")
+        );
+    }
+    if let Some(min) = settings.filters.min_size {
+        println!("{} {}", "Minimum size:".green(), format_size(min));
+    }
+    if let Some(max) = settings.filters.max_size {
+        println!("{} {}", "Maximum size:".green(), format_size(max));
+    }
+
+    println!(
+        "{} {} ({})",
+        "Processed files:".green(),
+        state.processed_files.len(),
+        format_size(state.processed_bytes())
+    );
+    if let Some((files, bytes)) = state.remaining() {
+        println!(
+            "{} {} ({})",
+            "Remaining files:".green(),
+            files,
+            format_size(bytes)
+        );
+    }
+
+    if !state.failed_files.is_empty() {
+        println!(
+            "{} {} (tried again on resume)",
+            "Failed files:".green(),
+            state.failed_files.len()
+        );
+    }
+
+    for partial in &state.in_flight {
+        println!(
+            "{} {} ({} of {})",
+            "In progress:".green(),
+            partial.source.display(),
+            format_size(partial.offset),
+            format_size(partial.size)
+        );
+    }
+
+    if list_files {
+        println!();
+        for file in &state.processed_files {
+            match &file.target {
+                Some(target) => println!("  {} → {}", file.path.display(), target.display()),
+                None => println!("  {}", file.path.display()),
+            }
+        }
+    }
+
+    let problems = check_save(state);
+    if problems.is_empty() {
+        println!("\n{}", "✅ Save file is valid".bright_green());
+    } else {
+        println!("\n{}", "⚠️  Problems found:".yellow());
+        for problem in problems {
+            println!("  {} {}", "-".yellow(), problem);
+        }
+    }
+
+    Ok(())
+}
+
+fn check_save(state: &SaveState) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    if !state.input_path.is_dir() {
+        problems.push(format!(
+            "Input folder {} no longer exists",
+            state.input_path.display()
+        ));
+    }
+
+    let mut seen = HashSet::new();
+    let duplicates = state
+        .processed_files
+        .iter()
+        .filter(|f| !seen.insert(&f.path))
+        .count();
+    if duplicates > 0 {
+        problems.push(format!("{} files are recorded more than once", duplicates));
+    }
+
+    let without_target = state
+        .processed_files
+        .iter()
+        .filter(|f| f.target.is_none())
+        .count();
+    if without_target > 0 {
+        problems.push(format!(
+            "{} files have no recorded output path and cannot be checked on resume",
+            without_target
+        ));
+    }
+
+    for partial in &state.in_flight {
+        if !temp_path_for(&partial.target).exists() {
+            problems.push(format!(
+                "Partial copy of {} is gone and will restart from the beginning",
+                partial.source.display()
+            ));
+        }
+    }
+
+    problems
+}
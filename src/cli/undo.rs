@@ -0,0 +1,109 @@
+use crate::{
+    OrganizeError,
+    runs::{Operation, list_runs, mark_undone, read_run, resolve_run, undo::undo_operations},
+    ui::print_undo_report,
+    vfs::RealFs,
+};
+use chrono::{DateTime, Local};
+use colored::*;
+use dialoguer::{Confirm, theme::ColorfulTheme};
+use std::path::Path;
+
+pub fn run_undo(run: Option<String>, yes: bool) -> Result<(), OrganizeError> {
+    match run {
+        Some(run) => undo_run(&resolve_run(&RealFs, &run), yes),
+        None => print_runs(),
+    }
+}
+
+fn print_runs() -> Result<(), OrganizeError> {
+    let runs = list_runs(&RealFs).unwrap_or_default();
+    if runs.is_empty() {
+        println!("{}", "No runs recorded yet.".yellow());
+        return Ok(());
+    }
+
+    println!("\n{}", "📜 Recorded runs:".bright_cyan());
+    for path in runs {
+        let id = path.file_stem().unwrap_or_default().to_string_lossy();
+        let Ok(operations) = read_run(&RealFs, &path) else {
+            println!("  {} {}", id, "(unreadable)".red());
+            continue;
+        };
+
+        let mut line = format!("  {}", id.bright_green());
+        if let Some(Operation::Start {
+            input, output, at, ..
+        }) = operations.first()
+        {
+            let at: DateTime<Local> = (*at).into();
+            line.push_str(&format!(
+                " {} {} → {}",
+                at.format("%Y-%m-%d %H:%M"),
+                input.display(),
+                output.display()
+            ));
+        }
+        if is_undone(&operations) {
+            line.push_str(&format!(" {}", "(undone)".dimmed()));
+        }
+        println!("{}", line);
+    }
+
+    println!(
+        "\nRun {} to reverse one",
+        "file-organizer undo <run>".bright_cyan()
+    );
+    Ok(())
+}
+
+fn undo_run(path: &Path, yes: bool) -> Result<(), OrganizeError> {
+    let operations = read_run(&RealFs, path).map_err(|error| OrganizeError::RunLog {
+        path: path.to_path_buf(),
+        error,
+    })?;
+    if is_undone(&operations) {
+        return Err(OrganizeError::UserInput(
+            "This run has already been undone".to_string(),
+        ));
+    }
+
+    let files = operations
+        .iter()
+        .filter(|op| matches!(op, Operation::Copy { .. } | Operation::Move { .. }))
+        .count();
+    let folders = operations
+        .iter()
+        .filter(|op| matches!(op, Operation::Mkdir { .. }))
+        .count();
+
+    let confirmed = yes
+        || Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!(
+                "Undo {} files and {} folders from this run?",
+                files, folders
+            ))
+            .default(false)
+            .interact()
+            .unwrap_or(false);
+    if !confirmed {
+        return Ok(());
+    }
+
+    let report = undo_operations(&RealFs, &operations);
+    // Left open while anything was left in place, so undo can be run again
+    if report.skipped.is_empty()
+        && let Err(e) = mark_undone(&RealFs, path)
+    {
+        eprintln!("{} {}", "Failed to mark the run as undone:".yellow(), e);
+    }
+
+    print_undo_report(&report);
+    Ok(())
+}
+
+fn is_undone(operations: &[Operation]) -> bool {
+    operations
+        .iter()
+        .any(|op| matches!(op, Operation::Undone { .. }))
+}
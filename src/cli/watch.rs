@@ -0,0 +1,237 @@
+use crate::{
+    OrganizeError,
+    index::FileIndex,
+    models::{CopyOptions, CustomFile, RunSettings, SaveState},
+    organizer::{Observer, Settler, process_files, remove_stale_temp_files, resumable_temp_files},
+    runs::{RunJournal, journal_path},
+    ui::TextLog,
+    utils::{generate_run_id, get_index_path},
+    vfs::{FileSystem, RealFs},
+};
+use colored::*;
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::Duration;
+
+// How often waiting files are looked at again
+const TICK: Duration = Duration::from_millis(250);
+
+// What stays the same for every batch of settled files
+struct Watch<'a> {
+    source: &'a Path,
+    destination: &'a Path,
+    settings: RunSettings,
+    options: CopyOptions,
+    index: Option<FileIndex>,
+    // Every batch adds to the same run log, so the whole watch can be undone
+    run_id: String,
+    stop_signal: Arc<AtomicBool>,
+    // Stops the batch being organized. A batch that aborts sets it too, which
+    // must not end the watch.
+    batch_stop: Arc<AtomicBool>,
+}
+
+pub fn run_watch(
+    source: PathBuf,
+    destination: PathBuf,
+    settle: Duration,
+    settings: RunSettings,
+    options: CopyOptions,
+    use_index: bool,
+) -> Result<(), OrganizeError> {
+    let fs = &RealFs;
+    if let Err(error) = fs.read_dir(&source) {
+        return Err(OrganizeError::Metadata {
+            path: source,
+            error,
+        });
+    }
+
+    // Ctrl+C, SIGTERM and SIGHUP finish the file being copied and stop
+    let stop_signal = Arc::new(AtomicBool::new(false));
+    let batch_stop = Arc::new(AtomicBool::new(false));
+    let (stop, stop_batch) = (Arc::clone(&stop_signal), Arc::clone(&batch_stop));
+    ctrlc::set_handler(move || {
+        stop_batch.store(true, Ordering::SeqCst);
+        stop.store(true, Ordering::SeqCst);
+    })
+    .map_err(|e| OrganizeError::Internal(format!("Could not set the signal handler: {}", e)))?;
+
+    let index = if use_index {
+        let path = get_index_path();
+        match FileIndex::open(fs, &path) {
+            Ok(index) => Some(index),
+            Err(error) => return Err(OrganizeError::Index { path, error }),
+        }
+    } else {
+        println!(
+            "{}",
+            "⚠️  Without the index, every file is copied again each time the watch starts".yellow()
+        );
+        None
+    };
+
+    // Watching starts before looking at what is already there, so nothing that
+    // arrives in between is missed
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx).map_err(|e| watch_error(&source, e))?;
+    watcher
+        .watch(&source, RecursiveMode::Recursive)
+        .map_err(|e| watch_error(&source, e))?;
+
+    println!(
+        "\n{} {} → {}",
+        "👀 Watching".bright_cyan(),
+        source.display(),
+        destination.display()
+    );
+    println!("Press Ctrl+C to stop");
+
+    let removed = remove_stale_temp_files(fs, &destination, &resumable_temp_files(fs));
+    if removed > 0 {
+        println!(
+            "{} {}",
+            "🧹 Removed leftover partial files:".yellow(),
+            removed
+        );
+    }
+
+    // Files already there go through the same wait as new ones. After a
+    // restart the index leaves alone the ones organized before.
+    let mut settler = Settler::new(settle, &source, &destination);
+    let found = settler.changed(fs, &source).len();
+    println!("found     {} files", found);
+
+    let watch = Watch {
+        source: &source,
+        destination: &destination,
+        settings,
+        options,
+        index,
+        run_id: format!("watch_{}", generate_run_id(&source)),
+        stop_signal,
+        batch_stop,
+    };
+    let mut observer = TextLog::new(io::stdout());
+
+    while !watch.stop_signal.load(Ordering::SeqCst) {
+        match rx.recv_timeout(TICK) {
+            Ok(event) => {
+                watch.note(&mut settler, event);
+                for event in rx.try_iter() {
+                    watch.note(&mut settler, event);
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+
+        let files = settler.settled(fs);
+        if !files.is_empty() {
+            watch.organize(files, &mut settler, &mut observer)?;
+        }
+    }
+
+    println!("\n{}", "👋 Stopped watching".bright_blue());
+    Ok(())
+}
+
+impl Watch<'_> {
+    fn note(&self, settler: &mut Settler, event: notify::Result<notify::Event>) {
+        let event = match event {
+            Ok(event) => event,
+            Err(e) => {
+                println!("error     {}", e);
+                return;
+            }
+        };
+        // Events were dropped, so everything is looked at again
+        if event.need_rescan() {
+            let found = settler.changed(&RealFs, self.source).len();
+            println!("rescan    {} files", found);
+            return;
+        }
+
+        for path in &event.paths {
+            match event.kind {
+                EventKind::Access(_) => {}
+                EventKind::Remove(_) => settler.forget(path),
+                _ => {
+                    for path in settler.changed(&RealFs, path) {
+                        println!("waiting   {}", path.display());
+                    }
+                }
+            }
+        }
+    }
+
+    // A file that fails is reported and tried again the next time it changes,
+    // only the run log failing stops the watch. Files the batch left for later
+    // go back to waiting.
+    fn organize(
+        &self,
+        files: Vec<CustomFile>,
+        settler: &mut Settler,
+        observer: &mut dyn Observer,
+    ) -> Result<(), OrganizeError> {
+        let journal = RunJournal::open(
+            &RealFs,
+            &self.run_id,
+            self.source,
+            self.destination,
+            self.settings.mode,
+        )
+        .map_err(|error| OrganizeError::RunLog {
+            path: journal_path(&self.run_id),
+            error,
+        })?;
+        let mut save_state = SaveState::new(
+            self.source.to_path_buf(),
+            self.destination.to_path_buf(),
+            self.settings.clone(),
+        );
+        save_state.run_id = Some(self.run_id.clone());
+
+        match process_files(
+            &RealFs,
+            files.into_iter(),
+            save_state,
+            None,
+            Some(journal),
+            self.index.as_ref(),
+            &self.options,
+            observer,
+            Arc::clone(&self.batch_stop),
+        ) {
+            Ok(report) => {
+                for warning in &report.warnings {
+                    println!("warning   {} {}", warning.path.display(), warning.message);
+                }
+                for path in &report.deferred {
+                    settler.changed(&RealFs, path);
+                }
+            }
+            Err(e) => println!("error     {}", e.with_causes()),
+        }
+        // Only a stop of the whole watch ends it, the next batch starts afresh
+        if !self.stop_signal.load(Ordering::SeqCst) {
+            self.batch_stop.store(false, Ordering::SeqCst);
+        }
+        Ok(())
+    }
+}
+
+fn watch_error(path: &Path, error: notify::Error) -> OrganizeError {
+    let error = match error.kind {
+        notify::ErrorKind::Io(error) => error,
+        _ => io::Error::other(error),
+    };
+    OrganizeError::Metadata {
+        path: path.to_path_buf(),
+        error,
+    }
+}
@@ -1,24 +1,129 @@
+use crate::models::FileOperation;
+use std::io;
+use std::path::PathBuf;
+
 #[derive(Debug)]
 pub enum OrganizeError {
     NoPathSelected,
-    InvalidFolderName,
-    NoParentDirectory,
-    DirectoryCreationFailed(String),
-    FileCopyFailed(String),
-    UserInputError(String),
-    InvalidOutputPath(String),
+    NoFilesFound(PathBuf),
+    UserInput(String),
+    // A file could not be brought across to its target
+    File {
+        operation: FileOperation,
+        source: PathBuf,
+        target: PathBuf,
+        error: io::Error,
+    },
+    // A source file's details, such as its creation date, could not be read
+    Metadata {
+        path: PathBuf,
+        error: io::Error,
+    },
+    SaveFile {
+        path: PathBuf,
+        error: io::Error,
+    },
+    RunLog {
+        path: PathBuf,
+        error: io::Error,
+    },
+    Index {
+        path: PathBuf,
+        error: io::Error,
+    },
+    // Failures of the program itself, like the progress screen breaking
+    Internal(String),
+}
+
+impl OrganizeError {
+    // Exit codes are part of the command line interface, never renumber them:
+    //   1  internal error
+    //   2  bad input, nothing selected or nothing to do
+    //   3  save file could not be read or written
+    //   4  run log could not be read or written
+    //   5  a source file could not be read
+    //   6  a target file could not be written
+    //   7  permission denied
+    //   8  out of disk space
+    //   9  file index could not be read or written
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::Internal(_) => 1,
+            Self::NoPathSelected | Self::NoFilesFound(_) | Self::UserInput(_) => 2,
+            Self::SaveFile { .. } => 3,
+            Self::RunLog { .. } => 4,
+            Self::Index { .. } => 9,
+            Self::File { error, .. } | Self::Metadata { error, .. }
+                if error.kind() == io::ErrorKind::PermissionDenied =>
+            {
+                7
+            }
+            Self::File { error, .. } if error.kind() == io::ErrorKind::StorageFull => 8,
+            Self::Metadata { .. }
+            | Self::File {
+                operation: FileOperation::ReadSource,
+                ..
+            } => 5,
+            Self::File { .. } => 6,
+        }
+    }
+
+    // The message followed by every error that caused it, which Display leaves
+    // to `source()`
+    pub fn with_causes(&self) -> String {
+        let mut message = self.to_string();
+        let mut cause = std::error::Error::source(self);
+        while let Some(error) = cause {
+            message.push_str(&format!(": {}", error));
+            cause = error.source();
+        }
+        message
+    }
+
+    fn io_error(&self) -> Option<&io::Error> {
+        match self {
+            Self::File { error, .. }
+            | Self::Metadata { error, .. }
+            | Self::SaveFile { error, .. }
+            | Self::RunLog { error, .. }
+            | Self::Index { error, .. } => Some(error),
+            _ => None,
+        }
+    }
 }
 
 impl std::fmt::Display for OrganizeError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::NoPathSelected => write!(f, "No folder selected"),
-            Self::InvalidFolderName => write!(f, "Invalid folder name"),
-            Self::NoParentDirectory => write!(f, "Could not determine parent directory"),
-            Self::DirectoryCreationFailed(e) => write!(f, "Failed to create directory: {}", e),
-            Self::FileCopyFailed(e) => write!(f, "Failed to copy file: {}", e),
-            Self::UserInputError(e) => write!(f, "User input error: {}", e),
-            Self::InvalidOutputPath(e) => write!(f, "Invalid output path: {}", e),
+            Self::NoFilesFound(path) => write!(f, "No files found in '{}'", path.display()),
+            Self::UserInput(e) => write!(f, "{}", e),
+            Self::File {
+                operation,
+                source,
+                target,
+                ..
+            } => write!(
+                f,
+                "Failed to copy '{}' to '{}' while {}",
+                source.display(),
+                target.display(),
+                operation
+            ),
+            Self::Metadata { path, .. } => {
+                write!(f, "Could not read details of '{}'", path.display())
+            }
+            Self::SaveFile { path, .. } => write!(f, "Save file '{}'", path.display()),
+            Self::RunLog { path, .. } => write!(f, "Run log '{}'", path.display()),
+            Self::Index { path, .. } => write!(f, "File index '{}'", path.display()),
+            Self::Internal(e) => write!(f, "{}", e),
         }
     }
-}
\ No newline at end of file
+}
+
+impl std::error::Error for OrganizeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.io_error()
+            .map(|error| error as &(dyn std::error::Error + 'static))
+    }
+}
@@ -1,34 +1,96 @@
-use colored::*;
-use std::{process, collections::HashSet, path::PathBuf};
 use crate::{
+    OrganizeError,
     models::SaveState,
-    organizer::get_all_files,
-    ui::{get_output_location, get_output_choice},
+    organizer::{
+        diff_against_save, get_all_files, remove_stale_temp_files, resumable_temp_files,
+        temp_path_for,
+    },
+    say,
+    ui::{ask_resume_choices, get_output_choice, get_output_location, print_resume_diff},
+    vfs::RealFs,
+};
+use colored::*;
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
 };
 
 pub struct InitResult {
     pub input_path: PathBuf,
     pub output_path: PathBuf,
-    pub files: Vec<crate::models::CustomFile>,
+    // None for a fresh run, which scans the input while it copies
+    pub files: Option<Vec<crate::models::CustomFile>>,
     pub resume_path: Option<PathBuf>,
     pub save_state: Option<SaveState>,
 }
 
-pub fn initialize_app(operation_mode: Option<PathBuf>) -> InitResult {
+pub fn initialize_app(
+    operation_mode: Option<PathBuf>,
+    verify_hash: bool,
+    scan_threads: usize,
+) -> Result<InitResult, OrganizeError> {
     match operation_mode {
-        Some(save_path) => match SaveState::load(&save_path) {
-            Ok(save_state) => {
-                println!("{}", "📝 Resuming from save file...".bright_green());
-                println!(
+        Some(save_path) => match SaveState::load(&RealFs, &save_path) {
+            Ok(mut save_state) => {
+                say!("{}", "📝 Resuming from save file...".bright_green());
+                say!(
                     "{} {}",
                     "Input folder:".green(),
                     save_state.input_path.display()
                 );
-                println!(
+                say!(
                     "{} {}",
                     "Output folder:".green(),
                     save_state.output_path.display()
                 );
+                say!(
+                    "{} {:?}, {:?}, {:?} on conflict",
+                    "Settings:".green(),
+                    save_state.settings.strategy,
+                    save_state.settings.mode,
+                    save_state.settings.conflict_policy
+                );
+
+                let mut resumable = resumable_temp_files(&RealFs);
+                resumable.extend(
+                    save_state
+                        .in_flight
+                        .iter()
+                        .map(|partial| temp_path_for(&partial.target)),
+                );
+                report_stale_temp_files(&save_state.output_path, &resumable);
+
+                say!(
+                    "\n{}",
+                    "🔍 Checking files against the save...".bright_cyan()
+                );
+                let all_files = get_all_files(&RealFs, &save_state.input_path, scan_threads);
+                let diff = diff_against_save(&RealFs, &save_state, &all_files, verify_hash);
+
+                let mut skipped_new = HashSet::new();
+                if !diff.is_empty() {
+                    print_resume_diff(&diff);
+                    let choices = ask_resume_choices(&diff);
+
+                    let mut forget: HashSet<_> = diff.vanished.iter().collect();
+                    if choices.recopy_changed {
+                        forget.extend(diff.changed.iter().map(|f| &f.path));
+                    }
+                    save_state
+                        .processed_files
+                        .retain(|f| !forget.contains(&f.path));
+
+                    if !choices.include_new {
+                        skipped_new.extend(diff.new.iter().cloned());
+                    }
+
+                    if let Err(error) = save_state.compact_to(&RealFs, &save_path) {
+                        return Err(OrganizeError::SaveFile {
+                            path: save_path,
+                            error,
+                        });
+                    }
+                }
 
                 let processed_paths: HashSet<_> = save_state
                     .processed_files
@@ -36,53 +98,62 @@ pub fn initialize_app(operation_mode: Option<PathBuf>) -> InitResult {
                     .map(|f| f.path.clone())
                     .collect();
 
-                let all_files = get_all_files(&save_state.input_path);
                 let remaining_files: Vec<_> = all_files
                     .into_iter()
                     .filter(|f| !processed_paths.contains(&f.path))
+                    .filter(|f| !skipped_new.contains(&f.path))
                     .collect();
 
-                InitResult {
+                Ok(InitResult {
                     input_path: save_state.input_path.clone(),
                     output_path: save_state.output_path.clone(),
-                    files: remaining_files,
+                    files: Some(remaining_files),
                     resume_path: Some(save_path),
                     save_state: Some(save_state),
-                }
-            }
-            Err(e) => {
-                eprintln!("{} {}", "Failed to load save file:".red(), e);
-                process::exit(1);
+                })
             }
+            Err(error) => Err(OrganizeError::SaveFile {
+                path: save_path,
+                error,
+            }),
         },
         None => {
-            let input_path = get_output_location()
-                .map(|paths| paths.input_path)
-                .expect("Failed to get input location");
+            let input_path = get_output_location()?.input_path;
 
-            println!(
+            say!(
                 "{} {}",
                 "Selected input folder:".green(),
                 input_path.display()
             );
 
             let output_path = get_output_choice(&input_path);
-            println!(
+            say!(
                 "{} {}",
                 "Selected output folder:".green(),
                 output_path.display()
             );
 
-            println!("\n{}", "🔍 Scanning files...".bright_cyan());
-            let files = get_all_files(&input_path);
+            // Other saves may still resume copies into the same folder
+            report_stale_temp_files(&output_path, &resumable_temp_files(&RealFs));
 
-            InitResult {
+            Ok(InitResult {
                 input_path,
                 output_path,
-                files,
+                files: None,
                 resume_path: None,
                 save_state: None,
-            }
+            })
         }
     }
-}
\ No newline at end of file
+}
+
+fn report_stale_temp_files(output_path: &Path, keep: &[PathBuf]) {
+    let removed = remove_stale_temp_files(&RealFs, output_path, keep);
+    if removed > 0 {
+        say!(
+            "{} {}",
+            "🧹 Removed leftover partial files:".yellow(),
+            removed
+        );
+    }
+}
@@ -1,49 +1,61 @@
-use colored::*;
-use dialoguer::{Select, theme::ColorfulTheme};
-use std::path::PathBuf;
 use crate::{
     OrganizeError,
-    models::SaveState,
-    ui::cleanup,
     cli::handle_error,
-    save::{save_progress, handle_save_cleanup},
+    models::{FailedFile, RunReport, RunWarning, SaveState},
+    organizer::temp_path_for,
+    save::{handle_save_cleanup, save_progress},
+    say,
+    ui::cleanup,
 };
+use colored::*;
+use dialoguer::{Select, theme::ColorfulTheme};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 
 pub fn handle_organization_result(
-    result: Result<Option<SaveState>, OrganizeError>,
+    result: Result<RunReport, OrganizeError>,
     resume_path: Option<PathBuf>,
+    save_path: PathBuf,
     output_path: PathBuf,
-) {
-    match result {
-        Ok(Some(mut save_state)) => {
-            println!("\n{}", "🛑 Process interrupted!".yellow());
+    run_id: String,
+    auto_save: bool,
+) -> ExitCode {
+    let report = match result {
+        Ok(report) => report,
+        // A resumed run's output still belongs to its save, so only a fresh
+        // run's checkpoint and output are cleaned up
+        Err(e) if resume_path.is_none() => {
+            remove_save(save_path);
+            return handle_error(e, Some(&run_id));
+        }
+        Err(e) => return handle_error(e, None),
+    };
 
-            match resume_path {
-                Some(path) => {
-                    if let Ok(mut existing_save) = SaveState::load(&path) {
-                        existing_save
-                            .processed_files
-                            .extend(save_state.processed_files);
-                        save_state = existing_save;
-                    }
+    print_warnings(&report.warnings);
+    print_failures(&report.failures);
+    print_deferred(&report.deferred);
+    if report.unchanged > 0 {
+        say!(
+            "\n{} {}",
+            "📇 Unchanged since an earlier run:".bright_cyan(),
+            report.unchanged
+        );
+    }
+    if report.retries > 0 {
+        say!(
+            "\n{} {}",
+            "🔁 Transient errors retried:".yellow(),
+            report.retries.to_string().yellow()
+        );
+    }
 
-                    if let Err(e) =
-                        std::fs::write(&path, serde_json::to_string_pretty(&save_state).unwrap())
-                    {
-                        handle_error(
-                            OrganizeError::FileCopyFailed(format!(
-                                "Failed to update save file: {}",
-                                e
-                            )),
-                            Some(&output_path),
-                        );
-                    }
-                    println!(
-                        "\n{}",
-                        "📝 Progress automatically saved to existing file.".bright_yellow()
-                    );
-                    println!("\n{}", "👋 Goodbye!".bright_blue());
-                }
+    let saved = match report.interrupted {
+        Some(save_state) => {
+            say!("\n{}", "🛑 Process interrupted!".yellow());
+
+            let saved = match resume_path {
+                Some(path) => save(save_state, &path),
+                None if auto_save => save(save_state, &save_path),
                 None => {
                     let options = vec!["Save progress and exit", "Just exit"];
                     let selection = Select::with_theme(&ColorfulTheme::default())
@@ -54,35 +66,138 @@ pub fn handle_organization_result(
                         .unwrap_or(1);
 
                     match selection {
-                        0 => {
-                            if let Err(e) = save_progress(save_state) {
-                                handle_error(e, Some(&output_path));
-                            }
-                            println!("\n{}", "👋 Goodbye!".bright_blue());
-                        }
+                        0 => save(save_state, &save_path),
                         _ => {
-                            cleanup(&output_path);
-                            println!("\n{}", "👋 Goodbye!".bright_blue());
+                            remove_save(save_path);
+                            for partial in &save_state.in_flight {
+                                let _ = std::fs::remove_file(temp_path_for(&partial.target));
+                            }
+                            cleanup(&run_id);
+                            Ok(())
                         }
                     }
                 }
+            };
+            if saved.is_ok() {
+                say!("\n{}", "👋 Goodbye!".bright_blue());
             }
+            saved
         }
-        Ok(None) => {
-            println!(
-                "\n{}",
-                "✨ Organization completed successfully!"
-                    .bright_green()
-                    .bold()
-            );
-            println!(
-                "{} {}",
-                "📍 Files organized at:".bright_cyan(),
-                output_path.display()
-            );
+        None => match report.failed {
+            // Keep the save so the failed files can be retried by resuming
+            Some(save_state) => {
+                say!(
+                    "\n{}",
+                    "⚠️  Organization finished, but some files could not be copied".yellow()
+                );
+                let saved = save(save_state, &save_path);
+                if saved.is_ok() {
+                    say!("Resume this save to try the failed files again");
+                }
+                saved
+            }
+            None => {
+                say!(
+                    "\n{}",
+                    "✨ Organization completed successfully!"
+                        .bright_green()
+                        .bold()
+                );
+                say!(
+                    "{} {}",
+                    "📍 Files organized at:".bright_cyan(),
+                    output_path.display()
+                );
 
-            handle_save_cleanup(resume_path);
-        }
-        Err(e) => handle_error(e, Some(&output_path)),
+                remove_save(save_path);
+                Ok(())
+            }
+        },
+    };
+
+    match saved {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => handle_error(e, None),
+    }
+}
+
+fn save(save_state: SaveState, save_path: &Path) -> Result<(), OrganizeError> {
+    save_progress(save_state, save_path)?;
+    say!(
+        "\n{} {}",
+        "📝 Progress saved at:".bright_yellow(),
+        save_path.display()
+    );
+    Ok(())
+}
+
+fn remove_save(save_path: PathBuf) {
+    if let Err(e) = handle_save_cleanup(Some(save_path)) {
+        eprintln!(
+            "{} {}",
+            "Failed to clean up save file:".yellow(),
+            e.with_causes()
+        );
+    }
+}
+
+fn print_failures(failures: &[FailedFile]) {
+    if failures.is_empty() {
+        return;
+    }
+
+    say!(
+        "\n{} {}",
+        "❌ Failed files:".red(),
+        failures.len().to_string().red()
+    );
+    for failure in failures {
+        say!(
+            "  {} {}",
+            failure.path.display(),
+            format!(
+                "{:?} while {} after {} {}: {}",
+                failure.kind,
+                failure.operation,
+                failure.attempts,
+                if failure.attempts == 1 {
+                    "attempt"
+                } else {
+                    "attempts"
+                },
+                failure.message
+            )
+            .dimmed()
+        );
+    }
+}
+
+fn print_deferred(deferred: &[PathBuf]) {
+    if deferred.is_empty() {
+        return;
+    }
+
+    say!(
+        "\n{} {}",
+        "⏳ Still being written, left for the next run:".yellow(),
+        deferred.len().to_string().yellow()
+    );
+    for path in deferred {
+        say!("  {}", path.display());
+    }
+}
+
+fn print_warnings(warnings: &[RunWarning]) {
+    if warnings.is_empty() {
+        return;
+    }
+
+    say!(
+        "\n{} {}",
+        "⚠️  Warnings:".yellow(),
+        warnings.len().to_string().yellow()
+    );
+    for warning in warnings {
+        say!("  {} {}", warning.path.display(), warning.message.dimmed());
     }
-}
\ No newline at end of file
+}
@@ -1,79 +1,253 @@
 use crate::{
-    cli::{handle_error, print_header, select_operation_mode},
+    OrganizeError,
+    cli::{
+        Args, Command, Output, handle_error, print_header, run_bench, run_saves_command, run_undo,
+        run_watch, select_operation_mode,
+    },
     handlers::{InitResult, handle_organization_result, initialize_app, spawn_processing_thread},
-    ui::progress::ProgressUI,
+    index::FileIndex,
+    models::SaveState,
+    organizer::Observer,
+    runs::{RunJournal, journal_path},
+    say,
+    ui::{JsonLog, ProgressObserver, ProgressUI, TextLog, send_human_output_to_stderr},
+    utils::{get_index_path, get_save_dir},
+    vfs::RealFs,
 };
+use clap::Parser;
 use colored::*;
+use std::io;
+use std::process::ExitCode;
 use std::sync::{
     Arc,
     atomic::{AtomicBool, Ordering},
+    mpsc,
 };
+use std::time::Duration;
 
-pub fn run_app() {
+pub fn run_app() -> ExitCode {
+    let mut args = Args::parse();
+    // Only the events go to stdout as JSON lines, everything else to stderr
+    if args.output == Output::Json {
+        send_human_output_to_stderr();
+    }
     print_header();
 
+    if let Some(command) = args.command.take() {
+        let result = match command {
+            Command::Saves { command } => run_saves_command(command),
+            Command::Undo { run, yes } => run_undo(run, yes),
+            Command::Bench { from, to, size } => run_bench(from, to, size),
+            Command::Watch {
+                source,
+                destination,
+                settle,
+            } => run_watch(
+                source,
+                destination,
+                Duration::from_secs(settle),
+                args.run_settings(),
+                args.copy_options(),
+                !args.no_index,
+            ),
+        };
+        return match result {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => handle_error(e, None),
+        };
+    }
+
     let stop_signal = Arc::new(AtomicBool::new(false));
+    let auto_save = Arc::new(AtomicBool::new(false));
     let stop_signal_clone = Arc::clone(&stop_signal);
+    let auto_save_clone = Arc::clone(&auto_save);
 
-    ctrlc::set_handler(move || {
+    // Ctrl+C, SIGTERM and SIGHUP all stop the run and save without asking
+    if let Err(e) = ctrlc::set_handler(move || {
+        auto_save_clone.store(true, Ordering::SeqCst);
         stop_signal_clone.store(true, Ordering::SeqCst);
-    })
-    .expect("Error setting Ctrl+C handler");
+    }) {
+        return handle_error(
+            OrganizeError::Internal(format!("Could not set the signal handler: {}", e)),
+            None,
+        );
+    }
 
     let InitResult {
-        input_path: _,
+        input_path,
         output_path,
         files,
         resume_path,
-        save_state: _,
-    } = initialize_app(select_operation_mode());
+        save_state,
+    } = match initialize_app(select_operation_mode(), args.hash, args.scan_threads) {
+        Ok(init) => init,
+        Err(e) => return handle_error(e, None),
+    };
 
-    if files.is_empty() {
-        handle_error(
-            "No files found in the selected directory",
-            Some(&output_path),
+    if let Some(files) = &files {
+        if files.is_empty() {
+            return handle_error(OrganizeError::NoFilesFound(input_path), None);
+        }
+        say!(
+            "{} {} {}",
+            "Found".green(),
+            files.len().to_string().bright_green(),
+            "files".green()
         );
     }
 
-    println!(
-        "{} {} {}",
-        "Found".green(),
-        files.len().to_string().bright_green(),
-        "files".green()
-    );
+    say!("\n{}", "📊 Organizing files...".bright_cyan());
+    if args.output == Output::Tui {
+        say!("Press 'q' to quit or Ctrl+C to stop the process");
+    } else {
+        say!("Press Ctrl+C to stop the process");
+    }
 
-    println!("\n{}", "📊 Organizing files...".bright_cyan());
-    println!("Press 'q' to quit or Ctrl+C to stop the process");
+    // Resumed runs keep checkpointing into the save they came from
+    let save_path = match &resume_path {
+        Some(path) => path.clone(),
+        None => match SaveState::new_save_path(&RealFs, &input_path) {
+            Ok(path) => path,
+            Err(e) => {
+                return handle_error(
+                    OrganizeError::SaveFile {
+                        path: get_save_dir(),
+                        error: e,
+                    },
+                    None,
+                );
+            }
+        },
+    };
 
-    let total_files = files.len() as u64;
-    let mut ui = ProgressUI::new(total_files).expect("Failed to create UI");
+    // Resumed runs keep the settings they were started with
+    let mut save_state = save_state
+        .unwrap_or_else(|| SaveState::new(input_path, output_path.clone(), args.run_settings()));
 
-    // Initialize the file queue with all files
-    let file_queue: Vec<(String, u64)> = files
-        .iter()
-        .map(|f| (f.name.clone(), f.meta.len()))
-        .collect();
-    ui.set_file_queue(file_queue);
+    // A run is named after its save file. Saves from before run logs existed
+    // start a new log when resumed.
+    let run_id = save_state
+        .run_id
+        .get_or_insert_with(|| {
+            save_path
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string()
+        })
+        .clone();
+    let journal = match RunJournal::open(
+        &RealFs,
+        &run_id,
+        &save_state.input_path,
+        &save_state.output_path,
+        save_state.settings.mode,
+    ) {
+        Ok(journal) => journal,
+        Err(e) => {
+            return handle_error(
+                OrganizeError::RunLog {
+                    path: journal_path(&run_id),
+                    error: e,
+                },
+                None,
+            );
+        }
+    };
 
-    let (handle, rx) = spawn_processing_thread(
-        files,
-        output_path.clone(),
-        Arc::clone(&stop_signal),
-        total_files,
-    );
-
-    if let Err(e) = ui.run(rx) {
-        handle_error(format!("UI error: {}", e), Some(&output_path));
-        return;
-    }
+    let index = if args.no_index {
+        None
+    } else {
+        let path = get_index_path();
+        match FileIndex::open(&RealFs, &path) {
+            Ok(index) => Some(index),
+            Err(error) => return handle_error(OrganizeError::Index { path, error }, None),
+        }
+    };
+
+    let options = args.copy_options();
+
+    // Only a fresh run's output can be cleaned up, a resumed one still
+    // belongs to its save
+    let cleanup_run = resume_path.is_none().then_some(run_id.as_str());
+
+    let joined = match args.output {
+        Output::Tui => {
+            let mut ui = match ProgressUI::new() {
+                Ok(ui) => ui,
+                Err(e) => {
+                    return handle_error(
+                        OrganizeError::Internal(format!("Failed to create UI: {}", e)),
+                        None,
+                    );
+                }
+            };
+
+            let (tx, rx) = mpsc::channel();
+            let handle = spawn_processing_thread(
+                files,
+                save_state,
+                save_path.clone(),
+                journal,
+                index,
+                Arc::clone(&stop_signal),
+                options,
+                Box::new(ProgressObserver::new(tx)),
+            );
+
+            let ui_result = ui.run(rx, &stop_signal, &auto_save);
+            if ui_result.is_err() {
+                // Let the copy stop before anything it wrote is cleaned up
+                stop_signal.store(true, Ordering::SeqCst);
+            }
+            let joined = handle.join();
+
+            // Restore the terminal before printing the summary
+            drop(ui);
 
-    let result = match handle.join() {
+            if let Err(e) = ui_result {
+                return handle_error(
+                    OrganizeError::Internal(format!("UI error: {}", e)),
+                    cleanup_run,
+                );
+            }
+            joined
+        }
+        Output::Text | Output::Json => {
+            let observer: Box<dyn Observer + Send> = match args.output {
+                Output::Json => Box::new(JsonLog::new(io::stdout())),
+                _ => Box::new(TextLog::new(io::stdout())),
+            };
+            spawn_processing_thread(
+                files,
+                save_state,
+                save_path.clone(),
+                journal,
+                index,
+                Arc::clone(&stop_signal),
+                options,
+                observer,
+            )
+            .join()
+        }
+    };
+
+    let result = match joined {
         Ok(r) => r,
         Err(_) => {
-            handle_error("Thread panicked", Some(&output_path));
-            return;
+            return handle_error(
+                OrganizeError::Internal("Thread panicked".to_string()),
+                cleanup_run,
+            );
         }
     };
 
-    handle_organization_result(result, resume_path, output_path);
+    handle_organization_result(
+        result,
+        resume_path,
+        save_path,
+        output_path,
+        run_id,
+        auto_save.load(Ordering::SeqCst),
+    )
 }
@@ -0,0 +1,183 @@
+use crate::models::{CustomFile, Mode};
+use crate::vfs::{FileSystem, WriteHandle};
+use redb::backends::FileBackend;
+use redb::{Database, StorageBackend, TableDefinition};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::time::SystemTime;
+
+// Source path to a JSON encoded `IndexEntry`
+const FILES: TableDefinition<&str, &[u8]> = TableDefinition::new("files");
+// Entries are committed together, every commit syncs the whole database
+const BATCH: usize = 256;
+
+// What a run did with a source file, and what the file looked like then
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct IndexEntry {
+    pub size: u64,
+    pub modified: SystemTime,
+    #[serde(default)]
+    pub inode: Option<u64>,
+    #[serde(default)]
+    pub hash: Option<String>,
+    pub target: PathBuf,
+    pub mode: Mode,
+    pub at: SystemTime,
+}
+
+impl IndexEntry {
+    // Whether `file` is still the file this entry was made for
+    pub fn matches(&self, file: &CustomFile) -> bool {
+        file.size == self.size
+            && file.modified == Some(self.modified)
+            && (file.inode.is_none() || self.inode.is_none() || file.inode == self.inode)
+    }
+}
+
+// Every file organized so far, kept across runs by source path so a re-run
+// only has to copy what is new or changed since
+pub struct FileIndex {
+    path: PathBuf,
+    db: Database,
+    // Entries not committed yet
+    pending: Mutex<Vec<(String, Vec<u8>)>>,
+}
+
+impl FileIndex {
+    pub fn open(fs: &dyn FileSystem, path: &Path) -> io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs.create_dir_all(parent)?;
+        }
+        let file = if fs.exists(path) {
+            fs.open_write(path)?
+        } else {
+            fs.create(path)?
+        };
+        // redb locks files on disk itself, so two runs can't share the index
+        let builder = Database::builder();
+        let db = match file.as_file() {
+            Some(file) => {
+                let backend = FileBackend::new(file.try_clone()?).map_err(to_io)?;
+                builder.create_with_backend(backend)
+            }
+            None => builder.create_with_backend(HandleBackend(Mutex::new(file))),
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            db: db.map_err(to_io)?,
+            pending: Mutex::new(Vec::new()),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn get(&self, source: &Path) -> io::Result<Option<IndexEntry>> {
+        let txn = self.db.begin_read().map_err(to_io)?;
+        let table = match txn.open_table(FILES) {
+            Ok(table) => table,
+            // Nothing was ever recorded
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(None),
+            Err(e) => return Err(to_io(e)),
+        };
+        match table.get(key(source).as_str()).map_err(to_io)? {
+            Some(value) => Ok(Some(serde_json::from_slice(value.value())?)),
+            None => Ok(None),
+        }
+    }
+
+    // Kept in memory until enough entries pile up or `flush` is called
+    pub fn record(&self, source: &Path, entry: &IndexEntry) -> io::Result<()> {
+        let mut pending = self.pending();
+        pending.push((key(source), serde_json::to_vec(entry)?));
+        if pending.len() >= BATCH {
+            self.commit(&mut pending)?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&self) -> io::Result<()> {
+        self.commit(&mut self.pending())
+    }
+
+    fn commit(&self, pending: &mut Vec<(String, Vec<u8>)>) -> io::Result<()> {
+        if pending.is_empty() {
+            return Ok(());
+        }
+        let txn = self.db.begin_write().map_err(to_io)?;
+        {
+            let mut table = txn.open_table(FILES).map_err(to_io)?;
+            for (source, entry) in pending.iter() {
+                table
+                    .insert(source.as_str(), entry.as_slice())
+                    .map_err(to_io)?;
+            }
+        }
+        txn.commit().map_err(to_io)?;
+        pending.clear();
+        Ok(())
+    }
+
+    fn pending(&self) -> MutexGuard<'_, Vec<(String, Vec<u8>)>> {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+// Keeps the database in a file that only the `FileSystem` can reach
+struct HandleBackend(Mutex<Box<dyn WriteHandle>>);
+
+impl HandleBackend {
+    fn handle(&self) -> MutexGuard<'_, Box<dyn WriteHandle>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl fmt::Debug for HandleBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("HandleBackend")
+    }
+}
+
+impl StorageBackend for HandleBackend {
+    fn len(&self) -> io::Result<u64> {
+        self.handle().seek(SeekFrom::End(0))
+    }
+
+    fn read(&self, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        let mut handle = self.handle();
+        let mut buf = vec![0; len];
+        handle.seek(SeekFrom::Start(offset))?;
+        handle.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+
+    fn set_len(&self, len: u64) -> io::Result<()> {
+        self.handle().set_len(len)
+    }
+
+    fn sync_data(&self, _eventual: bool) -> io::Result<()> {
+        self.handle().sync_data()
+    }
+
+    fn write(&self, offset: u64, data: &[u8]) -> io::Result<()> {
+        let mut handle = self.handle();
+        handle.seek(SeekFrom::Start(offset))?;
+        handle.write_all(data)
+    }
+}
+
+fn key(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+fn to_io(error: impl Into<redb::Error>) -> io::Error {
+    match error.into() {
+        redb::Error::Io(error) => error,
+        error => io::Error::other(error),
+    }
+}
@@ -1,10 +1,14 @@
 pub mod cli;
 pub mod error;
 pub mod handlers;
+pub mod index;
 pub mod models;
 pub mod organizer;
+pub mod runs;
 pub mod save;
 pub mod ui;
 pub mod utils;
+pub mod vfs;
 
 pub use error::OrganizeError;
+pub use organizer::Organizer;
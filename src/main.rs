@@ -1,5 +1,6 @@
 use file_organizer::handlers::run_app;
+use std::process::ExitCode;
 
-fn main() {
-    run_app();
+fn main() -> ExitCode {
+    run_app()
 }
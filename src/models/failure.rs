@@ -0,0 +1,74 @@
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::PathBuf;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileOperation {
+    ReadSource,
+    CreateDir,
+    WriteTarget,
+    Rename,
+}
+
+impl std::fmt::Display for FileOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ReadSource => write!(f, "reading the source"),
+            Self::CreateDir => write!(f, "creating the target folder"),
+            Self::WriteTarget => write!(f, "writing the target"),
+            Self::Rename => write!(f, "moving the file into place"),
+        }
+    }
+}
+
+// A file the run gave up on. Kept in the save so resuming tries it again.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FailedFile {
+    pub path: PathBuf,
+    pub target: PathBuf,
+    pub operation: FileOperation,
+    #[serde(with = "error_kind")]
+    pub kind: io::ErrorKind,
+    pub message: String,
+    pub attempts: u32,
+}
+
+// `io::ErrorKind` has no serde support, so it is stored by name
+mod error_kind {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::io::ErrorKind;
+
+    const KINDS: [ErrorKind; 19] = [
+        ErrorKind::NotFound,
+        ErrorKind::PermissionDenied,
+        ErrorKind::ConnectionRefused,
+        ErrorKind::ConnectionReset,
+        ErrorKind::ConnectionAborted,
+        ErrorKind::NotConnected,
+        ErrorKind::AlreadyExists,
+        ErrorKind::WouldBlock,
+        ErrorKind::InvalidInput,
+        ErrorKind::InvalidData,
+        ErrorKind::TimedOut,
+        ErrorKind::WriteZero,
+        ErrorKind::Interrupted,
+        ErrorKind::Unsupported,
+        ErrorKind::UnexpectedEof,
+        ErrorKind::OutOfMemory,
+        ErrorKind::StorageFull,
+        ErrorKind::ReadOnlyFilesystem,
+        ErrorKind::StaleNetworkFileHandle,
+    ];
+
+    pub fn serialize<S: Serializer>(kind: &ErrorKind, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{:?}", kind))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<ErrorKind, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Ok(KINDS
+            .into_iter()
+            .find(|kind| format!("{:?}", kind) == name)
+            .unwrap_or(ErrorKind::Other))
+    }
+}
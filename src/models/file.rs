@@ -1,32 +1,39 @@
-use std::{fs::{self, Metadata}, path::PathBuf};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use crate::vfs::FileMeta;
 use chrono::{DateTime, Local};
 use super::FileType;
 
+// Only the parts of a file's metadata a run looks at, so millions of scanned
+// files stay small
 #[derive(Debug)]
 pub struct CustomFile {
     pub extension: String,
     pub name: String,
     pub path: PathBuf,
-    pub meta: Metadata,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+    pub created: Option<SystemTime>,
+    // Identifies the disk or share the file lives on
+    pub device: Option<u64>,
+    pub inode: Option<u64>,
 }
 
 impl CustomFile {
-    pub fn from_path(path: &PathBuf) -> Option<Self> {
+    pub fn from_path(path: &Path, meta: FileMeta) -> Option<Self> {
         let file_name = path.file_name()?.to_str()?;
         let extension = path.extension()?.to_str()?;
 
-        let metadata = fs::metadata(path)
-            .map_err(|e| {
-                eprintln!("Failed to read metadata for '{}': {}", path.display(), e);
-                e
-            })
-            .ok()?;
-
         Some(CustomFile {
             name: file_name.to_string(),
             extension: extension.to_string(),
-            path: path.clone(),
-            meta: metadata,
+            path: path.to_path_buf(),
+            size: meta.len(),
+            modified: meta.modified().ok(),
+            created: meta.created().ok(),
+            device: meta.device(),
+            inode: meta.inode(),
         })
     }
 
@@ -34,16 +41,15 @@ impl CustomFile {
         FileType::from_extension(&self.extension)
     }
 
-    pub fn get_creation_date(&self) -> Result<String, String> {
-        let created = self.meta.created().map_err(|e| {
-            format!(
-                "Failed to get creation time for '{}': {}",
-                self.path.display(),
-                e
+    pub fn get_creation_date(&self) -> io::Result<String> {
+        let created = self.created.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Unsupported,
+                "creation time is not available on this file system",
             )
         })?;
 
         let datetime: DateTime<Local> = created.into();
         Ok(datetime.format("%Y-%m-%d").to_string())
     }
-}
\ No newline at end of file
+}
@@ -1,11 +1,22 @@
+mod failure;
 mod file;
 mod file_type;
+mod options;
 mod organized_file;
 mod paths;
+mod report;
 mod save_state;
+mod settings;
 
+pub use failure::{FailedFile, FileOperation};
 pub use file::CustomFile;
 pub use file_type::FileType;
+pub use options::{
+    CheckpointOptions, ConcurrencyOptions, CopyMethod, CopyOptions, ErrorPolicy, PreserveOptions,
+    RetryOptions,
+};
 pub use organized_file::OrganizedFile;
 pub use paths::Paths;
-pub use save_state::SaveState;
+pub use report::{RunReport, RunWarning};
+pub use save_state::{InFlightFile, ProcessedFile, SaveState};
+pub use settings::{ConflictPolicy, Filters, Mode, RunSettings, Strategy};
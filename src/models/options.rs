@@ -0,0 +1,122 @@
+use clap::ValueEnum;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Default)]
+pub struct CopyOptions {
+    pub fsync: bool,
+    // Record a content hash of every copied file so resume can verify it
+    pub hash: bool,
+    pub preserve: PreserveOptions,
+    pub checkpoint: CheckpointOptions,
+    pub on_error: ErrorPolicy,
+    // Extra attempts per file under `ErrorPolicy::Retry`
+    pub retries: u32,
+    pub retry: RetryOptions,
+    pub concurrency: ConcurrencyOptions,
+    pub method: CopyMethod,
+    // Files modified more recently than this may still be being written, they
+    // are reported as deferred and left for a later run
+    pub quiet_period: Duration,
+}
+
+// What to do when a single file can't be copied
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    // Stop the whole run
+    #[default]
+    Abort,
+    // Note the failure and carry on with the next file
+    Skip,
+    // Try the file again a few times, then skip it
+    Retry,
+}
+
+// How the bytes get from the source to the target
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CopyMethod {
+    // The fastest one the platform and both file systems support
+    #[default]
+    Auto,
+    // Linux copy_file_range, which lets the file system copy or clone the data itself
+    CopyFileRange,
+    // Linux sendfile, copying within the kernel
+    Sendfile,
+    // Reading into a buffer and writing it out, works everywhere
+    Buffered,
+}
+
+// Backoff for errors a network file system usually recovers from, tried
+// before the error policy gets a say
+#[derive(Debug, Clone)]
+pub struct RetryOptions {
+    // Retries per file, 0 turns them off
+    pub limit: u32,
+    // Doubled after every retry of the same file
+    pub base_delay: Duration,
+}
+
+impl Default for RetryOptions {
+    fn default() -> Self {
+        Self {
+            limit: 5,
+            base_delay: Duration::from_millis(500),
+        }
+    }
+}
+
+// How many files are copied at the same time
+#[derive(Debug, Clone)]
+pub struct ConcurrencyOptions {
+    pub jobs: usize,
+    // Copies reading from or writing to any one device. Left unset, spinning
+    // disks get one at a time and everything else up to `jobs`.
+    pub per_device: Option<usize>,
+    // Folders read at the same time while scanning the input
+    pub scan_threads: usize,
+}
+
+impl Default for ConcurrencyOptions {
+    fn default() -> Self {
+        Self {
+            jobs: 4,
+            per_device: None,
+            scan_threads: 8,
+        }
+    }
+}
+
+// The save file is rewritten after whichever limit is reached first
+#[derive(Debug, Clone)]
+pub struct CheckpointOptions {
+    pub every_files: usize,
+    pub interval: Duration,
+}
+
+impl Default for CheckpointOptions {
+    fn default() -> Self {
+        Self {
+            every_files: 100,
+            interval: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PreserveOptions {
+    pub times: bool,
+    pub permissions: bool,
+    // Only applied when running as root
+    pub ownership: bool,
+    pub xattrs: bool,
+}
+
+impl Default for PreserveOptions {
+    fn default() -> Self {
+        Self {
+            times: true,
+            permissions: true,
+            ownership: true,
+            xattrs: true,
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use std::path::PathBuf;
+use std::time::SystemTime;
 
 #[derive(Debug)]
 pub struct OrganizedFile {
@@ -6,4 +7,10 @@ pub struct OrganizedFile {
     pub target_path: PathBuf,
     pub file_name: String,
     pub size: u64,
-}
\ No newline at end of file
+    // Device of the source file, used to spread copies across disks
+    pub device: Option<u64>,
+    // Inode of the source file, recorded in the index
+    pub inode: Option<u64>,
+    // Modification time of the source when it was scanned
+    pub modified: Option<SystemTime>,
+}
@@ -0,0 +1,34 @@
+use super::{FailedFile, SaveState};
+use std::path::PathBuf;
+
+#[derive(Debug, Clone)]
+pub struct RunWarning {
+    pub path: PathBuf,
+    pub message: String,
+}
+
+#[derive(Default)]
+pub struct RunReport {
+    // Files brought across in this session
+    pub copied: usize,
+    // Files an earlier run organized that haven't changed since
+    pub unchanged: usize,
+    pub interrupted: Option<SaveState>,
+    // The state to keep when the run got through every file but some failed
+    pub failed: Option<SaveState>,
+    pub warnings: Vec<RunWarning>,
+    pub failures: Vec<FailedFile>,
+    // Files still being written, left for a later run
+    pub deferred: Vec<PathBuf>,
+    // Transient errors that were retried, over all files
+    pub retries: u32,
+}
+
+impl RunReport {
+    pub fn warn(&mut self, path: PathBuf, message: impl Into<String>) {
+        self.warnings.push(RunWarning {
+            path,
+            message: message.into(),
+        });
+    }
+}
@@ -1,72 +1,200 @@
+use super::{FailedFile, RunSettings};
+use crate::save::format::{self, DecodedSave};
+use crate::save::journal;
 use crate::utils::{ensure_save_dir, generate_save_filename, get_save_dir};
+use crate::vfs::FileSystem;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
-use std::{fs, io};
+use std::collections::HashSet;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct ProcessedFile {
     pub path: PathBuf,
     pub name: String,
     pub size: u64,
     pub modified: std::time::SystemTime,
+    #[serde(default)]
+    pub target: Option<PathBuf>,
+    #[serde(default)]
+    pub hash: Option<String>,
+}
+
+// A file that was partway through copying when the run stopped. `offset` is
+// the number of bytes flushed to its temporary file.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct InFlightFile {
+    pub source: PathBuf,
+    pub target: PathBuf,
+    pub size: u64,
+    pub modified: std::time::SystemTime,
+    pub offset: u64,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct SaveState {
     pub input_path: PathBuf,
     pub output_path: PathBuf,
+    #[serde(default)]
     pub processed_files: Vec<ProcessedFile>,
+    #[serde(default)]
+    pub settings: RunSettings,
+    // Several files are copied at once, so several can be left partway
+    #[serde(default)]
+    pub in_flight: Vec<InFlightFile>,
+    #[serde(default)]
+    pub saved_at: Option<std::time::SystemTime>,
+    // Size of the whole run, done and not done, as of the last plan
+    #[serde(default)]
+    pub total_files: Option<u64>,
+    #[serde(default)]
+    pub total_bytes: Option<u64>,
+    // Name of the run log that records what this run changed on disk
+    #[serde(default)]
+    pub run_id: Option<String>,
+    // Files the last session failed on; they are not in `processed_files` so
+    // a resumed run tries them again
+    #[serde(default)]
+    pub failed_files: Vec<FailedFile>,
+    // The journal this state was last written to or loaded from, and how many
+    // processed entries it holds
+    #[serde(skip)]
+    journal: Option<(PathBuf, usize)>,
 }
 
 impl SaveState {
-    pub fn new(input_path: PathBuf, output_path: PathBuf) -> Self {
+    pub fn new(input_path: PathBuf, output_path: PathBuf, settings: RunSettings) -> Self {
         Self {
             input_path,
             output_path,
             processed_files: Vec::new(),
+            settings,
+            in_flight: Vec::new(),
+            saved_at: None,
+            total_files: None,
+            total_bytes: None,
+            run_id: None,
+            failed_files: Vec::new(),
+            journal: None,
         }
     }
 
-    pub fn add_processed_file(
-        &mut self,
-        path: PathBuf,
-        name: String,
-        size: u64,
-        modified: std::time::SystemTime,
-    ) {
-        self.processed_files.push(ProcessedFile {
-            path,
-            name,
-            size,
-            modified,
-        });
+    pub fn add_processed_file(&mut self, file: ProcessedFile) {
+        self.processed_files.push(file);
+    }
+
+    pub fn save(&mut self, fs: &dyn FileSystem) -> io::Result<PathBuf> {
+        let save_path = Self::new_save_path(fs, &self.input_path)?;
+        self.save_to(fs, &save_path)?;
+
+        Ok(save_path)
     }
 
-    pub fn save(&self) -> io::Result<PathBuf> {
-        ensure_save_dir()?;
+    pub fn new_save_path(fs: &dyn FileSystem, input_path: &Path) -> io::Result<PathBuf> {
+        ensure_save_dir(fs)?;
+        Ok(get_save_dir().join(generate_save_filename(input_path)))
+    }
 
-        let save_dir = get_save_dir();
-        let filename = generate_save_filename(&self.input_path);
-        let save_path = save_dir.join(filename);
+    // Appends the files finished since the last write when the journal at
+    // `save_path` is ours, otherwise writes the whole journal
+    pub fn save_to(&mut self, fs: &dyn FileSystem, save_path: &Path) -> io::Result<()> {
+        let written = match &self.journal {
+            Some((path, written))
+                if path == save_path && *written <= self.processed_files.len() =>
+            {
+                *written
+            }
+            _ => return self.compact_to(fs, save_path),
+        };
 
-        let json = serde_json::to_string_pretty(self)?;
-        fs::write(&save_path, json)?;
+        self.saved_at = Some(std::time::SystemTime::now());
+        self.journal = None;
 
-        Ok(save_path)
+        let file = fs.open_append(save_path)?;
+        let mut writer = BufWriter::new(file);
+        journal::append(&mut writer, self, written)?;
+        writer
+            .into_inner()
+            .map_err(|e| e.into_error())?
+            .sync_data()?;
+
+        self.journal = Some((save_path.to_path_buf(), self.processed_files.len()));
+        Ok(())
+    }
+
+    // Rewrites the journal from scratch. Needed whenever entries were removed,
+    // and writes to a temporary file first so a crash mid-write never leaves a
+    // half-written save behind.
+    pub fn compact_to(&mut self, fs: &dyn FileSystem, save_path: &Path) -> io::Result<()> {
+        self.saved_at = Some(std::time::SystemTime::now());
+        self.journal = None;
+
+        let temp_path = save_path.with_extension("forg.tmp");
+        let mut writer = BufWriter::new(fs.create(&temp_path)?);
+        journal::write_full(&mut writer, self, format::CURRENT_VERSION)?;
+        writer
+            .into_inner()
+            .map_err(|e| e.into_error())?
+            .sync_all()?;
+        fs.rename(&temp_path, save_path)?;
+
+        self.journal = Some((save_path.to_path_buf(), self.processed_files.len()));
+        Ok(())
+    }
+
+    // Writes the state as a single JSON document, the format used before journals
+    pub fn export_json(&self, fs: &dyn FileSystem, path: &Path) -> io::Result<()> {
+        let mut file = fs.create(path)?;
+        file.write_all(format::encode_json(self)?.as_bytes())?;
+        file.sync_all()
+    }
+
+    pub fn load(fs: &dyn FileSystem, save_path: &Path) -> io::Result<Self> {
+        Self::load_versioned(fs, save_path).map(|decoded| decoded.state)
+    }
+
+    // Loads a save of any supported version, upgrading it to the current one
+    pub fn load_versioned(fs: &dyn FileSystem, save_path: &Path) -> io::Result<DecodedSave> {
+        let content = fs.read_to_string(save_path)?;
+        let mut decoded =
+            format::decode(&content).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        if decoded.appendable {
+            let written = decoded.state.processed_files.len();
+            decoded.state.journal = Some((save_path.to_path_buf(), written));
+        }
+        Ok(decoded)
     }
 
-    pub fn load(save_path: &PathBuf) -> io::Result<Self> {
-        let content = fs::read_to_string(save_path)?;
-        serde_json::from_str(&content).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    pub fn processed_bytes(&self) -> u64 {
+        self.processed_files.iter().map(|f| f.size).sum()
     }
 
-    pub fn list_saves() -> io::Result<Vec<PathBuf>> {
-        let save_dir = get_save_dir();
-        let entries = fs::read_dir(save_dir)?;
+    // Files and bytes still to copy, when the save knows the run's totals
+    pub fn remaining(&self) -> Option<(u64, u64)> {
+        let files = self.total_files?;
+        let bytes = self.total_bytes?;
+        let partial: u64 = self.in_flight.iter().map(|f| f.offset).sum();
+
+        Some((
+            files.saturating_sub(self.processed_files.len() as u64),
+            bytes.saturating_sub(self.processed_bytes() + partial),
+        ))
+    }
+
+    // Output paths this run has already finished, which a re-plan must treat
+    // as taken by the run
+    pub fn claimed_targets(&self) -> HashSet<PathBuf> {
+        self.processed_files
+            .iter()
+            .filter_map(|f| f.target.clone())
+            .collect()
+    }
 
-        Ok(entries
-            .filter_map(|entry| entry.ok())
-            .map(|entry| entry.path())
+    pub fn list_saves(fs: &dyn FileSystem) -> io::Result<Vec<PathBuf>> {
+        Ok(fs
+            .read_dir(&get_save_dir())?
+            .into_iter()
             .filter(|path| path.extension().and_then(|ext| ext.to_str()) == Some("forg"))
             .collect())
     }
@@ -0,0 +1,57 @@
+use super::CustomFile;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+// Everything that decides where files end up. Saved with the progress so a
+// resumed run plans exactly what the original run planned.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct RunSettings {
+    pub strategy: Strategy,
+    pub filters: Filters,
+    pub conflict_policy: ConflictPolicy,
+    pub mode: Mode,
+}
+
+#[derive(Serialize, Deserialize, ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Strategy {
+    // <output>/<type>/<date>/<name>
+    #[default]
+    TypeDate,
+    // <output>/<type>/<name>
+    Type,
+    // <output>/<date>/<name>
+    Date,
+}
+
+#[derive(Serialize, Deserialize, ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    #[default]
+    Overwrite,
+    Skip,
+    Rename,
+}
+
+#[derive(Serialize, Deserialize, ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Mode {
+    #[default]
+    Copy,
+    Move,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Filters {
+    // Lowercase extensions to keep, empty keeps everything
+    pub extensions: Vec<String>,
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+}
+
+impl Filters {
+    pub fn matches(&self, file: &CustomFile) -> bool {
+        let size = file.size;
+
+        (self.extensions.is_empty() || self.extensions.contains(&file.extension.to_lowercase()))
+            && self.min_size.is_none_or(|min| size >= min)
+            && self.max_size.is_none_or(|max| size <= max)
+    }
+}
@@ -0,0 +1,79 @@
+use crate::models::{CheckpointOptions, SaveState};
+use crate::runs::RunJournal;
+use crate::vfs::FileSystem;
+use std::io;
+use std::path::PathBuf;
+use std::time::Instant;
+
+pub struct Checkpointer<'a> {
+    fs: &'a dyn FileSystem,
+    save_path: Option<PathBuf>,
+    options: CheckpointOptions,
+    files_since: usize,
+    last_write: Instant,
+    journal: Option<RunJournal>,
+}
+
+impl<'a> Checkpointer<'a> {
+    pub fn new(
+        fs: &'a dyn FileSystem,
+        save_path: Option<PathBuf>,
+        options: CheckpointOptions,
+        journal: Option<RunJournal>,
+    ) -> Self {
+        Self {
+            fs,
+            save_path,
+            options,
+            files_since: 0,
+            last_write: Instant::now(),
+            journal,
+        }
+    }
+
+    pub fn journal(&mut self) -> Option<&mut RunJournal> {
+        self.journal.as_mut()
+    }
+
+    pub fn save_path(&self) -> Option<PathBuf> {
+        self.save_path.clone()
+    }
+
+    // Called after every finished file, writes the save state once enough files
+    // or time have gone by
+    pub fn file_done(&mut self, save_state: &mut SaveState) -> io::Result<()> {
+        self.files_since += 1;
+
+        if self.files_since >= self.options.every_files
+            || self.last_write.elapsed() >= self.options.interval
+        {
+            self.write(save_state)?;
+        }
+        Ok(())
+    }
+
+    // Called while a file is copied, once its partial file was flushed, writes
+    // the save state once enough time has gone by
+    pub fn copying(&mut self, save_state: &mut SaveState) -> io::Result<()> {
+        if self.last_write.elapsed() >= self.options.interval {
+            self.write(save_state)?;
+        }
+        Ok(())
+    }
+
+    // The run log is flushed first so the save never lists a file the log
+    // could have lost
+    pub fn write(&mut self, save_state: &mut SaveState) -> io::Result<()> {
+        self.files_since = 0;
+        self.last_write = Instant::now();
+
+        if let Some(journal) = &self.journal {
+            journal.sync()?;
+        }
+
+        match &self.save_path {
+            Some(path) => save_state.save_to(self.fs, path),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use super::events::Observer;
+use super::processor::process_files;
+use super::scanner::scan_files;
+use crate::error::OrganizeError;
+use crate::index::FileIndex;
+use crate::models::{
+    ConflictPolicy, CopyOptions, Filters, Mode, RunReport, RunSettings, SaveState, Strategy,
+};
+use crate::runs::{RunJournal, journal_path};
+use crate::vfs::{FileSystem, RealFs};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+
+// The engine without the command line around it: scans, plans and copies in
+// one call. It never prints or exits, everything comes back as the report, an
+// error or events passed to `run`.
+pub struct Organizer {
+    source: PathBuf,
+    destination: PathBuf,
+    settings: RunSettings,
+    options: CopyOptions,
+    save_path: Option<PathBuf>,
+    run_id: Option<String>,
+    index_path: Option<PathBuf>,
+    stop_signal: Arc<AtomicBool>,
+    file_system: Arc<dyn FileSystem>,
+}
+
+impl Organizer {
+    pub fn new(source: impl Into<PathBuf>, destination: impl Into<PathBuf>) -> Self {
+        Self {
+            source: source.into(),
+            destination: destination.into(),
+            settings: RunSettings::default(),
+            options: CopyOptions::default(),
+            save_path: None,
+            run_id: None,
+            index_path: None,
+            stop_signal: Arc::new(AtomicBool::new(false)),
+            file_system: Arc::new(RealFs),
+        }
+    }
+
+    pub fn strategy(mut self, strategy: Strategy) -> Self {
+        self.settings.strategy = strategy;
+        self
+    }
+
+    pub fn mode(mut self, mode: Mode) -> Self {
+        self.settings.mode = mode;
+        self
+    }
+
+    pub fn conflict_policy(mut self, conflict_policy: ConflictPolicy) -> Self {
+        self.settings.conflict_policy = conflict_policy;
+        self
+    }
+
+    pub fn filters(mut self, filters: Filters) -> Self {
+        self.settings.filters = filters;
+        self
+    }
+
+    pub fn options(mut self, options: CopyOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    // Checkpoint progress to this save file so a stopped run can be resumed
+    pub fn save_to(mut self, save_path: impl Into<PathBuf>) -> Self {
+        self.save_path = Some(save_path.into());
+        self
+    }
+
+    // Log every change under this run id so the run can be undone
+    pub fn log_run(mut self, run_id: impl Into<String>) -> Self {
+        self.run_id = Some(run_id.into());
+        self
+    }
+
+    // Leave alone files the index at this path has unchanged since an earlier
+    // run, and add every copied file to it
+    pub fn index(mut self, index_path: impl Into<PathBuf>) -> Self {
+        self.index_path = Some(index_path.into());
+        self
+    }
+
+    // Run against another file system than the disk, such as a `MemoryFs`
+    pub fn file_system(mut self, file_system: Arc<dyn FileSystem>) -> Self {
+        self.file_system = file_system;
+        self
+    }
+
+    // Setting the flag from another thread stops the run after the current chunk
+    pub fn stop_signal(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.stop_signal)
+    }
+
+    pub fn run(self, mut observer: impl Observer) -> Result<RunReport, OrganizeError> {
+        let fs = &*self.file_system;
+        // The scanner skips what it can't read, so a bad source would look empty
+        if let Err(error) = fs.read_dir(&self.source) {
+            return Err(OrganizeError::Metadata {
+                path: self.source,
+                error,
+            });
+        }
+
+        let files = scan_files(fs, &self.source, self.options.concurrency.scan_threads);
+        let mut save_state = SaveState::new(self.source, self.destination, self.settings.clone());
+        save_state.run_id = self.run_id.clone();
+
+        let journal = match &self.run_id {
+            Some(run_id) => Some(
+                RunJournal::open(
+                    fs,
+                    run_id,
+                    &save_state.input_path,
+                    &save_state.output_path,
+                    self.settings.mode,
+                )
+                .map_err(|error| OrganizeError::RunLog {
+                    path: journal_path(run_id),
+                    error,
+                })?,
+            ),
+            None => None,
+        };
+        let index = match &self.index_path {
+            Some(path) => Some(FileIndex::open(fs, path).map_err(|error| OrganizeError::Index {
+                path: path.clone(),
+                error,
+            })?),
+            None => None,
+        };
+
+        process_files(
+            fs,
+            files,
+            save_state,
+            self.save_path,
+            journal,
+            index.as_ref(),
+            &self.options,
+            &mut observer,
+            self.stop_signal,
+        )
+    }
+}
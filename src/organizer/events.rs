@@ -0,0 +1,119 @@
+use crate::models::{ConflictPolicy, FailedFile};
+use serde::{Serialize, Serializer};
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+// Everything a run reports while it works, in the order it happens. Scanning,
+// planning and copying overlap, so their events are interleaved. `index`
+// counts planned files from 1.
+#[derive(Serialize, Debug)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    // Scan, with the files found so far every now and then
+    Scanning {
+        files: usize,
+        bytes: u64,
+    },
+    Scanned {
+        files: usize,
+        bytes: u64,
+    },
+
+    // Plan
+    Conflict {
+        source: &'a Path,
+        target: &'a Path,
+        resolution: ConflictPolicy,
+    },
+    Skipped {
+        source: &'a Path,
+        target: &'a Path,
+    },
+    FileQueued {
+        index: usize,
+        source: &'a Path,
+        target: &'a Path,
+        size: u64,
+    },
+    // Every scanned file has been planned
+    Planned {
+        files: usize,
+        bytes: u64,
+        skipped: usize,
+        // Left alone since an earlier run organized them
+        unchanged: usize,
+    },
+
+    // Copy
+    FileStarted {
+        index: usize,
+        source: &'a Path,
+        target: &'a Path,
+        size: u64,
+    },
+    FileProgress {
+        index: usize,
+        copied: u64,
+        size: u64,
+    },
+    // A transient error, the file is tried again after `delay`
+    FileRetry {
+        index: usize,
+        attempt: u32,
+        #[serde(rename = "delay_ms", serialize_with = "millis")]
+        delay: Duration,
+        #[serde(serialize_with = "display")]
+        error: &'a io::Error,
+    },
+    FileFailed {
+        index: usize,
+        failure: &'a FailedFile,
+    },
+    // The source is still being written, a later run picks it up
+    FileDeferred {
+        index: usize,
+        source: &'a Path,
+    },
+
+    // Verify, the copy matched the source's length and hashed to `hash`
+    FileVerified {
+        index: usize,
+        target: &'a Path,
+        hash: Option<&'a str>,
+    },
+    FileFinished {
+        index: usize,
+        source: &'a Path,
+        target: &'a Path,
+        size: u64,
+        replaced: bool,
+    },
+
+    // Finalize
+    Finished {
+        copied: usize,
+        failed: usize,
+        warnings: usize,
+        retries: u32,
+        interrupted: bool,
+    },
+}
+
+pub trait Observer {
+    fn on_event(&mut self, event: &Event);
+}
+
+impl<F: FnMut(&Event)> Observer for F {
+    fn on_event(&mut self, event: &Event) {
+        self(event)
+    }
+}
+
+fn millis<S: Serializer>(delay: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(delay.as_millis() as u64)
+}
+
+fn display<S: Serializer>(error: &&io::Error, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(error)
+}
@@ -0,0 +1,21 @@
+use crate::vfs::FileSystem;
+use std::io::{self, Read};
+use std::path::Path;
+
+pub fn hash_file(fs: &dyn FileSystem, path: &Path) -> io::Result<String> {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update_reader(fs.open(path)?)?;
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+// Feeds the first `len` bytes of a file into the hasher, used to compare a
+// partial file with its source before a copy resumes into it
+pub fn hash_prefix(
+    fs: &dyn FileSystem,
+    path: &Path,
+    len: u64,
+    hasher: &mut blake3::Hasher,
+) -> io::Result<()> {
+    hasher.update_reader(fs.open(path)?.take(len))?;
+    Ok(())
+}
@@ -0,0 +1,107 @@
+use crate::models::PreserveOptions;
+use std::fs::{File, FileTimes, Metadata};
+use std::path::Path;
+
+// Copies metadata from the source onto the freshly written file and returns a
+// message for every attribute that could not be preserved
+pub fn preserve_metadata(
+    source: &Path,
+    source_meta: &Metadata,
+    target_file: &File,
+    options: &PreserveOptions,
+) -> Vec<String> {
+    let mut warnings = Vec::new();
+
+    if options.xattrs {
+        copy_xattrs(source, target_file, &mut warnings);
+    }
+
+    if options.ownership {
+        copy_ownership(source_meta, target_file, &mut warnings);
+    }
+
+    if options.permissions
+        && let Err(e) = target_file.set_permissions(source_meta.permissions())
+    {
+        warnings.push(format!("Could not preserve permissions: {}", e));
+    }
+
+    // Times go last because every other change touches the file
+    if options.times {
+        let mut times = FileTimes::new();
+        if let Ok(accessed) = source_meta.accessed() {
+            times = times.set_accessed(accessed);
+        }
+        if let Ok(modified) = source_meta.modified() {
+            times = times.set_modified(modified);
+        }
+
+        if let Err(e) = target_file.set_times(times) {
+            warnings.push(format!("Could not preserve timestamps: {}", e));
+        }
+    }
+
+    warnings
+}
+
+#[cfg(unix)]
+fn copy_ownership(source_meta: &Metadata, target_file: &File, warnings: &mut Vec<String>) {
+    use std::os::unix::fs::MetadataExt;
+
+    // Changing ownership needs root, so regular users silently keep their own
+    // SAFETY: geteuid takes no arguments, cannot fail and touches no memory
+    if unsafe { libc::geteuid() } != 0 {
+        return;
+    }
+
+    if let Err(e) = std::os::unix::fs::fchown(
+        target_file,
+        Some(source_meta.uid()),
+        Some(source_meta.gid()),
+    ) {
+        warnings.push(format!("Could not preserve ownership: {}", e));
+    }
+}
+
+#[cfg(not(unix))]
+fn copy_ownership(_source_meta: &Metadata, _target_file: &File, _warnings: &mut Vec<String>) {}
+
+#[cfg(unix)]
+fn copy_xattrs(source: &Path, target_file: &File, warnings: &mut Vec<String>) {
+    use xattr::FileExt;
+
+    if !xattr::SUPPORTED_PLATFORM {
+        return;
+    }
+
+    let names = match xattr::list(source) {
+        Ok(names) => names,
+        // A file system without extended attributes has none to preserve
+        Err(e)
+            if e.kind() == std::io::ErrorKind::Unsupported
+                || e.raw_os_error() == Some(libc::ENOTSUP) =>
+        {
+            return;
+        }
+        Err(e) => {
+            warnings.push(format!("Could not read extended attributes: {}", e));
+            return;
+        }
+    };
+
+    for name in names {
+        let result = xattr::get(source, &name)
+            .and_then(|value| target_file.set_xattr(&name, &value.unwrap_or_default()));
+
+        if let Err(e) = result {
+            warnings.push(format!(
+                "Could not preserve extended attribute '{}': {}",
+                name.to_string_lossy(),
+                e
+            ));
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn copy_xattrs(_source: &Path, _target_file: &File, _warnings: &mut Vec<String>) {}
@@ -1,5 +1,25 @@
+mod checkpoint;
+mod engine;
+mod events;
+mod hash;
+mod metadata;
+mod planner;
 mod processor;
+mod retry;
 mod scanner;
+mod scheduler;
+mod temp;
+mod transfer;
+mod validate;
+mod watch;
 
-pub use processor::{copy_files, organize_files};
-pub use scanner::get_all_files;
+pub use engine::Organizer;
+pub use events::{Event, Observer};
+pub use hash::hash_file;
+pub use planner::{Conflict, Planned, Planner};
+pub use processor::process_files;
+pub use scanner::{get_all_files, is_partial_download, scan_files};
+pub use temp::{is_temp_file, remove_stale_temp_files, resumable_temp_files, temp_path_for};
+pub use transfer::copy_file_with;
+pub use validate::{ChangeReason, ChangedFile, ResumeDiff, diff_against_save};
+pub use watch::Settler;
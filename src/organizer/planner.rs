@@ -0,0 +1,174 @@
+use crate::error::OrganizeError;
+use crate::index::{FileIndex, IndexEntry};
+use crate::models::{ConflictPolicy, CustomFile, OrganizedFile, RunSettings, Strategy};
+use crate::vfs::FileSystem;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+// What became of one scanned file
+pub enum Planned {
+    Queued {
+        file: OrganizedFile,
+        conflict: Option<Conflict>,
+    },
+    // Left out because its target was taken and the policy is to skip
+    Skipped {
+        file: OrganizedFile,
+        conflict: Conflict,
+    },
+    // Left out by the run's filters
+    Filtered,
+    // Organized by an earlier run and not changed since
+    Unchanged,
+}
+
+// A file whose first choice of target was already taken
+pub struct Conflict {
+    pub source: PathBuf,
+    pub target: PathBuf,
+    pub resolution: ConflictPolicy,
+}
+
+// Works out where files go, one at a time as the scan finds them, without
+// touching the output. Conflicts are settled first come, first served, so the
+// files must arrive in the scan's stable order.
+pub struct Planner<'a> {
+    fs: &'a dyn FileSystem,
+    output_path: &'a Path,
+    settings: &'a RunSettings,
+    index: Option<&'a FileIndex>,
+    // Targets handed out so far, including those an earlier part of the same
+    // run finished, so a resumed run resolves conflicts exactly as the
+    // original did
+    claimed: HashSet<PathBuf>,
+}
+
+impl<'a> Planner<'a> {
+    pub fn new(
+        fs: &'a dyn FileSystem,
+        output_path: &'a Path,
+        settings: &'a RunSettings,
+        index: Option<&'a FileIndex>,
+        claimed: HashSet<PathBuf>,
+    ) -> Self {
+        Self {
+            fs,
+            output_path,
+            settings,
+            index,
+            claimed,
+        }
+    }
+
+    pub fn plan(&mut self, file: CustomFile) -> Result<Planned, OrganizeError> {
+        if !self.settings.filters.matches(&file) {
+            return Ok(Planned::Filtered);
+        }
+        // A lookup that fails just means the file is copied again
+        let earlier = self
+            .index
+            .and_then(|index| index.get(&file.path).ok().flatten())
+            .filter(|entry| entry.target.starts_with(self.output_path));
+        if let Some(entry) = &earlier
+            && self.unchanged(&file, entry)
+        {
+            return Ok(Planned::Unchanged);
+        }
+        // A changed file's copy from an earlier run is brought up to date
+        // rather than kept beside the new one
+        let own_copy = earlier.map(|entry| entry.target);
+
+        let dir = target_dir(&file, self.output_path, self.settings.strategy)?;
+        let mut organized = OrganizedFile {
+            target_path: dir.join(&file.name),
+            source_path: file.path,
+            file_name: file.name,
+            size: file.size,
+            device: file.device,
+            inode: file.inode,
+            modified: file.modified,
+        };
+
+        let taken = |path: &Path| {
+            self.claimed.contains(path)
+                || (own_copy.as_deref() != Some(path) && self.fs.exists(path))
+        };
+
+        let mut conflict = None;
+        if taken(&organized.target_path) {
+            let found = Conflict {
+                source: organized.source_path.clone(),
+                target: organized.target_path.clone(),
+                resolution: self.settings.conflict_policy,
+            };
+            match self.settings.conflict_policy {
+                ConflictPolicy::Overwrite => {}
+                ConflictPolicy::Skip => {
+                    return Ok(Planned::Skipped {
+                        file: organized,
+                        conflict: found,
+                    });
+                }
+                ConflictPolicy::Rename => {
+                    organized.target_path = free_name(&dir, &organized.file_name, taken);
+                }
+            }
+            conflict = Some(found);
+        }
+
+        self.claimed.insert(organized.target_path.clone());
+        Ok(Planned::Queued {
+            file: organized,
+            conflict,
+        })
+    }
+
+    // Whether the file is as it was when `entry` was recorded, and its copy is
+    // still there
+    fn unchanged(&self, file: &CustomFile, entry: &IndexEntry) -> bool {
+        entry.matches(file)
+            && self
+                .fs
+                .metadata(&entry.target)
+                .is_ok_and(|meta| meta.len() == entry.size)
+    }
+}
+
+fn target_dir(
+    file: &CustomFile,
+    output_path: &Path,
+    strategy: Strategy,
+) -> Result<PathBuf, OrganizeError> {
+    let type_dir = || output_path.join(format!("{:?}", file.get_type()));
+    let date = || {
+        file.get_creation_date()
+            .map_err(|error| OrganizeError::Metadata {
+                path: file.path.clone(),
+                error,
+            })
+    };
+
+    Ok(match strategy {
+        Strategy::TypeDate => type_dir().join(date()?),
+        Strategy::Type => type_dir(),
+        Strategy::Date => output_path.join(date()?),
+    })
+}
+
+// "name.ext" becomes "name (1).ext", "name (2).ext" and so on
+fn free_name(dir: &Path, file_name: &str, taken: impl Fn(&Path) -> bool) -> PathBuf {
+    let name = Path::new(file_name);
+    let stem = name
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let extension = name
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+
+    (1..)
+        .map(|n| dir.join(format!("{} ({}){}", stem, n, extension)))
+        .find(|candidate| !taken(candidate))
+        .expect("ran out of file names")
+}
@@ -1,144 +1,1251 @@
+use super::checkpoint::Checkpointer;
+use super::events::{Event, Observer};
+use super::hash::{hash_file, hash_prefix};
+use super::metadata::preserve_metadata;
+use super::planner::{Conflict, Planned, Planner};
+use super::retry::{self, Backoff};
+use super::scanner::recently_modified;
+use super::scheduler::Scheduler;
+use super::temp::temp_path_for;
+use super::transfer::{self, During, FileError, Transfer};
 use crate::error::OrganizeError;
-use crate::models::{CustomFile, OrganizedFile, SaveState};
-use rayon::prelude::*;
-use std::fs::{self, File};
-use std::io::{Read, Write};
-use std::path::PathBuf;
+use crate::index::{FileIndex, IndexEntry};
+use crate::models::{
+    CopyOptions, CustomFile, ErrorPolicy, FailedFile, FileOperation, InFlightFile, Mode,
+    OrganizedFile, ProcessedFile, RunReport, SaveState,
+};
+use crate::runs::{Operation, RunJournal, Written};
+use crate::vfs::{FileSystem, ReadHandle, WriteHandle};
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 
-const BUFFER_SIZE: usize = 8192;
-
-pub fn organize_files(
-    files: Vec<CustomFile>,
-    output_path: &PathBuf,
-) -> Result<Vec<OrganizedFile>, OrganizeError> {
-    files
-        .par_iter() // Using rayon for parallel processing
-        .map(|file| {
-            let file_type = file.get_type();
-            let date = file
-                .get_creation_date()
-                .map_err(|e| OrganizeError::UserInputError(e))?;
-
-            let type_dir = output_path.join(format!("{:?}", file_type));
-            let date_dir = type_dir.join(date);
-
-            fs::create_dir_all(&date_dir)
-                .map_err(|e| OrganizeError::DirectoryCreationFailed(e.to_string()))?;
-
-            let target_path = date_dir.join(&file.name);
-
-            Ok(OrganizedFile {
-                source_path: file.path.clone(),
-                target_path,
-                file_name: file.name.clone(),
-                size: file.meta.len(),
-            })
-        })
-        .collect()
+// How much of a partial file is compared with the source before appending to it
+const RESUME_VERIFY_SIZE: u64 = 1024 * 1024;
+// Scanned files waiting for the planner
+const SCAN_BUFFER: usize = 1024;
+const SCAN_PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+// Progress of the file being copied, before it is tied to its index
+enum FileProgress {
+    Bytes(u64),
+    // Everything up to this offset is on disk, so a save can list the partial
+    // file as resumable from there
+    Synced(u64),
+    Retry {
+        attempt: u32,
+        delay: Duration,
+        error: io::Error,
+    },
 }
 
-pub fn copy_files<F>(
-    organized_files: Vec<OrganizedFile>,
-    mut progress_callback: F,
+// What every step of copying one file shares
+struct CopyContext<'a> {
+    fs: &'a dyn FileSystem,
+    options: &'a CopyOptions,
+    stop_signal: &'a AtomicBool,
+    backoff: &'a mut Backoff,
+}
+
+enum CopyOutcome {
+    Copied {
+        warnings: Vec<String>,
+        hash: Option<String>,
+        // Whether the bytes were copied and checked, rather than renamed
+        verified: bool,
+    },
+    Cancelled {
+        bytes_copied: u64,
+    },
+    // The source changed since the scan or while it was copied, so it is
+    // still being written. Nothing is left behind at the target.
+    Deferred,
+}
+
+// What the copy workers share
+struct Workers<'a> {
+    fs: &'a dyn FileSystem,
+    // Partial files from the last run, which stay resumable until finished
+    resume: &'a [InFlightFile],
+    moving: bool,
+    // The device the output lives on, when known
+    destination: Option<u64>,
+    options: &'a CopyOptions,
+    stop_signal: &'a AtomicBool,
+    scheduler: &'a Scheduler,
+}
+
+// Sent from the scanner, the planner and the workers to the thread that owns
+// the save state and observer
+enum Message {
+    Scanning { files: usize, bytes: u64 },
+    Scanned { files: usize, bytes: u64 },
+    Conflict(Conflict),
+    Skipped(OrganizedFile),
+    Unchanged,
+    // Sent before the file is handed to the workers
+    Queued(usize, Arc<OrganizedFile>),
+    // The planner got through everything the scanner sent
+    Planned,
+    PlanFailed(OrganizeError),
+    Started(usize),
+    Progress(usize, FileProgress),
+    Rename(Rename),
+    Done(Attempted),
+}
+
+// Asks for a file being moved within one file system to be renamed into place
+struct Rename {
+    index: usize,
+    replaced: bool,
+    // What the target will look like, for the run log
+    written: Written,
+    // Told whether the file was renamed
+    reply: Sender<bool>,
+}
+
+// A file after a worker is through with it
+struct Attempted {
+    index: usize,
+    replaced: bool,
+    attempts: u32,
+    retries: u32,
+    // Folders made for the file, for the run log
+    created_dirs: Vec<PathBuf>,
+    // None when the run was stopped while the file waited to be tried again
+    result: Option<Result<(CopyOutcome, u64, SystemTime), FileError>>,
+}
+
+// How a file ended, kept until the files before it have ended too so the
+// observer hears about them in plan order
+enum Ending {
+    Copied {
+        replaced: bool,
+        verified: bool,
+        hash: Option<String>,
+    },
+    Failed(FailedFile),
+    Deferred,
+    // Left for the next run to pick up
+    Unfinished,
+}
+
+// Records what the workers did, on the thread that owns the save state, the
+// run log and the observer
+struct Recorder<'a> {
+    fs: &'a dyn FileSystem,
+    index: Option<&'a FileIndex>,
+    // Told as files end in order, so workers don't run far ahead of them
+    scheduler: &'a Scheduler,
+    // Files queued and not yet reported as ended, by index
+    files: HashMap<usize, Arc<OrganizedFile>>,
+    moving: bool,
+    options: &'a CopyOptions,
+    save_state: SaveState,
+    checkpointer: Checkpointer<'a>,
+    report: RunReport,
+    observer: &'a mut dyn Observer,
+    endings: BTreeMap<usize, Ending>,
+    next_ending: usize,
+    // Files copied or given up on for good
+    settled: usize,
+    // Files and bytes finished by earlier sessions of the run
+    earlier: (usize, u64),
+    queued: usize,
+    queued_bytes: u64,
+    skipped: usize,
+    unchanged: usize,
+    // Files found, once the scan got through the whole input
+    scanned: Option<usize>,
+    // Set once every scanned file has been planned
+    planned: bool,
+}
+
+// Scans, plans and copies `files`, continuing `save_state` when resuming. The
+// stages run at the same time, so copying starts with the first planned file
+// and only a bounded number of files wait between them. The state is
+// checkpointed to `save_path` as files finish so a hard stop can be resumed,
+// and every change to the file system is logged to `journal` for undo. Files
+// `index` has unchanged since an earlier run are left alone, and every copied
+// file is added to it.
+#[allow(clippy::too_many_arguments)]
+pub fn process_files(
+    fs: &dyn FileSystem,
+    files: impl Iterator<Item = CustomFile> + Send,
+    save_state: SaveState,
+    save_path: Option<PathBuf>,
+    journal: Option<RunJournal>,
+    index: Option<&FileIndex>,
+    options: &CopyOptions,
+    observer: &mut dyn Observer,
     stop_signal: Arc<AtomicBool>,
-) -> Result<Option<SaveState>, OrganizeError>
-where
-    F: FnMut(&str, u64, u64, usize),
-{
-    let mut save_state = SaveState::new(
-        organized_files
-            .first()
-            .map(|f| {
-                f.source_path
-                    .parent()
-                    .unwrap_or(&f.source_path)
-                    .to_path_buf()
-            })
-            .unwrap_or_default(),
-        organized_files
-            .first()
-            .map(|f| {
-                f.target_path
-                    .parent()
-                    .unwrap_or(&f.target_path)
-                    .to_path_buf()
-            })
-            .unwrap_or_default(),
+) -> Result<RunReport, OrganizeError> {
+    let report = run_pipeline(
+        fs,
+        files,
+        save_state,
+        save_path,
+        journal,
+        index,
+        options,
+        observer,
+        stop_signal,
+    )?;
+
+    observer.on_event(&Event::Finished {
+        copied: report.copied,
+        failed: report.failures.len(),
+        warnings: report.warnings.len(),
+        retries: report.retries,
+        interrupted: report.interrupted.is_some(),
+    });
+    Ok(report)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run_pipeline(
+    fs: &dyn FileSystem,
+    files: impl Iterator<Item = CustomFile> + Send,
+    mut save_state: SaveState,
+    save_path: Option<PathBuf>,
+    journal: Option<RunJournal>,
+    index: Option<&FileIndex>,
+    options: &CopyOptions,
+    observer: &mut dyn Observer,
+    stop_signal: Arc<AtomicBool>,
+) -> Result<RunReport, OrganizeError> {
+    let resume = save_state.in_flight.clone();
+    // Failures from an earlier session are retried as part of this one
+    save_state.failed_files.clear();
+
+    // The output folder may not exist yet, so go by the closest folder that does
+    let destination = save_state
+        .output_path
+        .ancestors()
+        .find_map(|dir| fs.metadata(dir).ok())
+        .and_then(|meta| meta.device());
+    let scheduler = Scheduler::new(destination, &options.concurrency);
+    let workers = Workers {
+        fs,
+        resume: &resume,
+        moving: save_state.settings.mode == Mode::Move,
+        destination,
+        options,
+        stop_signal: &stop_signal,
+        scheduler: &scheduler,
+    };
+    // The recorder owns the save state, the planner works from a copy of what it needs
+    let output_path = save_state.output_path.clone();
+    let settings = save_state.settings.clone();
+    let planner = Planner::new(
+        fs,
+        &output_path,
+        &settings,
+        index,
+        save_state.claimed_targets(),
     );
+    let stop_signal = &*stop_signal;
 
-    for (index, file) in organized_files.into_iter().enumerate() {
-        if stop_signal.load(Ordering::SeqCst) && index > 0 {
-            return Ok(Some(save_state));
+    let mut recorder = Recorder {
+        fs,
+        index,
+        scheduler: &scheduler,
+        files: HashMap::new(),
+        moving: workers.moving,
+        options,
+        earlier: (
+            save_state.processed_files.len(),
+            save_state.processed_bytes(),
+        ),
+        save_state,
+        checkpointer: Checkpointer::new(fs, save_path, options.checkpoint.clone(), journal),
+        report: RunReport::default(),
+        observer,
+        endings: BTreeMap::new(),
+        next_ending: 0,
+        settled: 0,
+        queued: 0,
+        queued_bytes: 0,
+        skipped: 0,
+        unchanged: 0,
+        scanned: None,
+        planned: false,
+    };
+
+    let mut aborted = None;
+    thread::scope(|scope| {
+        let (tx, rx) = mpsc::channel();
+        let (scan_tx, scan_rx) = mpsc::sync_channel(SCAN_BUFFER);
+
+        let scanner_tx = tx.clone();
+        scope.spawn(move || scan(files, scan_tx, scanner_tx, stop_signal));
+        let planner_tx = tx.clone();
+        let scheduler = &scheduler;
+        scope.spawn(move || plan(planner, scan_rx, planner_tx, scheduler, stop_signal));
+        for _ in 0..options.concurrency.jobs.max(1) {
+            let tx = tx.clone();
+            let workers = &workers;
+            scope.spawn(move || workers.run(tx));
         }
+        drop(tx);
 
-        // Copy file with progress
-        copy_file_with_progress(
-            &file.source_path,
-            &file.target_path,
-            &file.file_name,
-            file.size,
-            |bytes_copied| {
-                progress_callback(&file.file_name, file.size, bytes_copied, index + 1);
-            },
-        )?;
+        for message in rx {
+            match message {
+                Message::Scanning { files, bytes } => recorder
+                    .observer
+                    .on_event(&Event::Scanning { files, bytes }),
+                Message::Scanned { files, bytes } => recorder.scanned(files, bytes),
+                Message::Conflict(conflict) => recorder.conflict(&conflict),
+                Message::Skipped(file) => recorder.skipped(file),
+                Message::Unchanged => recorder.unchanged += 1,
+                Message::Queued(index, file) => recorder.queued(index, file),
+                Message::Planned => recorder.planned(),
+                Message::Started(index) => recorder.started(index),
+                Message::Progress(index, progress) => recorder.progress(index, progress),
+                Message::Rename(rename) => {
+                    let renamed = recorder.rename(rename.index, rename.replaced, rename.written);
+                    let _ = rename.reply.send(renamed);
+                }
+                Message::PlanFailed(e) if aborted.is_none() => {
+                    stop_signal.store(true, Ordering::SeqCst);
+                    aborted = Some(e);
+                }
+                Message::PlanFailed(_) => {}
+                // Files that finish after an abort are still recorded, they
+                // are on disk and have to be resumable and undoable
+                Message::Done(attempted) => {
+                    if let Err(e) = recorder.done(attempted)
+                        && aborted.is_none()
+                    {
+                        // Stops the other workers after their current chunk
+                        stop_signal.store(true, Ordering::SeqCst);
+                        aborted = Some(e);
+                    }
+                }
+            }
+        }
+    });
+    if let Some(error) = aborted {
+        recorder.abort();
+        return Err(error);
+    }
+    if recorder.scanned == Some(0) {
+        return Err(OrganizeError::NoFilesFound(
+            recorder.save_state.input_path.clone(),
+        ));
+    }
 
-        // Call progress callback with final state
-        progress_callback(&file.file_name, file.size, file.size, index);
-        let source_path = file.source_path.clone();
+    Ok(recorder.finish())
+}
 
-        // Add to save state
-        save_state.add_processed_file(
+// Feeds the planner, reporting how far the scan got every so often
+fn scan(
+    files: impl Iterator<Item = CustomFile>,
+    planner: SyncSender<CustomFile>,
+    tx: Sender<Message>,
+    stop_signal: &AtomicBool,
+) {
+    let (mut count, mut bytes) = (0, 0);
+    let mut reported = Instant::now();
+    for file in files {
+        if stop_signal.load(Ordering::SeqCst) {
+            return;
+        }
+        count += 1;
+        bytes += file.size;
+        if reported.elapsed() >= SCAN_PROGRESS_INTERVAL {
+            let _ = tx.send(Message::Scanning {
+                files: count,
+                bytes,
+            });
+            reported = Instant::now();
+        }
+        // The planner is gone when the run was stopped or failed
+        if planner.send(file).is_err() {
+            return;
+        }
+    }
+    let _ = tx.send(Message::Scanned {
+        files: count,
+        bytes,
+    });
+}
+
+// Plans files in the order the scanner found them and queues them for the
+// workers, who can't finish before the queue is closed
+fn plan(
+    mut planner: Planner,
+    scanned: Receiver<CustomFile>,
+    tx: Sender<Message>,
+    scheduler: &Scheduler,
+    stop_signal: &AtomicBool,
+) {
+    let mut index = 0;
+    let finished = 'plan: {
+        for file in scanned {
+            match planner.plan(file) {
+                Ok(Planned::Queued { file, conflict }) => {
+                    if let Some(conflict) = conflict {
+                        let _ = tx.send(Message::Conflict(conflict));
+                    }
+                    let file = Arc::new(file);
+                    let _ = tx.send(Message::Queued(index, Arc::clone(&file)));
+                    if !scheduler.push(index, file, stop_signal) {
+                        break 'plan false;
+                    }
+                    index += 1;
+                }
+                Ok(Planned::Skipped { file, conflict }) => {
+                    let _ = tx.send(Message::Conflict(conflict));
+                    let _ = tx.send(Message::Skipped(file));
+                }
+                Ok(Planned::Unchanged) => {
+                    let _ = tx.send(Message::Unchanged);
+                }
+                Ok(Planned::Filtered) => {}
+                Err(e) => {
+                    let _ = tx.send(Message::PlanFailed(e));
+                    break 'plan false;
+                }
+            }
+        }
+        true
+    };
+
+    scheduler.close();
+    if finished {
+        let _ = tx.send(Message::Planned);
+    }
+}
+
+impl Workers<'_> {
+    fn run(&self, tx: Sender<Message>) {
+        while let Some(slot) = self.scheduler.next(self.stop_signal) {
+            let _ = tx.send(Message::Started(slot.index));
+            let attempted = self.attempt(slot.index, &slot.file, &tx);
+            // Free the devices before the file is recorded
+            drop(slot);
+            if tx.send(Message::Done(attempted)).is_err() {
+                return;
+            }
+        }
+    }
+
+    fn attempt(&self, index: usize, file: &OrganizedFile, tx: &Sender<Message>) -> Attempted {
+        let replaced = self.fs.exists(&file.target_path);
+        // A rename can't take a file to another device, so it isn't tried
+        let renaming = self.moving
+            && !matches!((file.device, self.destination), (Some(from), Some(to)) if from != to);
+        let mut created_dirs = Vec::new();
+        let mut backoff = Backoff::new(&self.options.retry);
+        let mut attempts = 0;
+
+        let result = loop {
+            attempts += 1;
+            let result = copy_one(
+                file,
+                self.resume,
+                renaming,
+                &mut CopyContext {
+                    fs: self.fs,
+                    options: self.options,
+                    stop_signal: self.stop_signal,
+                    backoff: &mut backoff,
+                },
+                &mut created_dirs,
+                |progress| {
+                    let _ = tx.send(Message::Progress(index, progress));
+                },
+                |written| {
+                    let (reply, renamed) = mpsc::channel();
+                    tx.send(Message::Rename(Rename {
+                        index,
+                        replaced,
+                        written,
+                        reply,
+                    }))
+                    .is_ok()
+                        && renamed.recv().unwrap_or(false)
+                },
+            );
+
+            let stopped = self.stop_signal.load(Ordering::SeqCst);
+            // Errors from opening, creating folders or renaming start the file over
+            let delay = match &result {
+                Err(e) if !stopped => backoff.next_delay(&e.error),
+                _ => None,
+            };
+            match (delay, result) {
+                (Some(delay), Err(e)) => {
+                    let _ = tx.send(Message::Progress(
+                        index,
+                        FileProgress::Retry {
+                            attempt: backoff.attempts(),
+                            delay,
+                            error: e.error,
+                        },
+                    ));
+                    if retry::wait(delay, self.stop_signal) {
+                        continue;
+                    }
+                    // Stopped while waiting, the file is started over on resume
+                    break None;
+                }
+                (_, result) => {
+                    if result.is_err()
+                        && self.options.on_error == ErrorPolicy::Retry
+                        && attempts <= self.options.retries
+                        && !stopped
+                    {
+                        continue;
+                    }
+                    break Some(result);
+                }
+            }
+        };
+
+        Attempted {
+            index,
+            replaced,
+            attempts,
+            retries: backoff.attempts(),
+            created_dirs,
+            result,
+        }
+    }
+}
+
+impl Recorder<'_> {
+    fn scanned(&mut self, files: usize, bytes: u64) {
+        self.scanned = Some(files);
+        self.observer.on_event(&Event::Scanned { files, bytes });
+    }
+
+    fn conflict(&mut self, conflict: &Conflict) {
+        self.observer.on_event(&Event::Conflict {
+            source: &conflict.source,
+            target: &conflict.target,
+            resolution: conflict.resolution,
+        });
+    }
+
+    fn skipped(&mut self, file: OrganizedFile) {
+        self.skipped += 1;
+        self.observer.on_event(&Event::Skipped {
+            source: &file.source_path,
+            target: &file.target_path,
+        });
+        self.report.warn(
             file.source_path,
-            file.file_name,
-            file.size,
-            std::fs::metadata(source_path)
-                .map_err(|e| OrganizeError::FileCopyFailed(e.to_string()))?
-                .modified()
-                .unwrap_or_else(|_| std::time::SystemTime::now()),
+            format!("Skipped, {} already exists", file.target_path.display()),
         );
     }
 
-    Ok(None)
+    fn queued(&mut self, index: usize, file: Arc<OrganizedFile>) {
+        self.queued += 1;
+        self.queued_bytes += file.size;
+        self.observer.on_event(&Event::FileQueued {
+            index: index + 1,
+            source: &file.source_path,
+            target: &file.target_path,
+            size: file.size,
+        });
+        self.files.insert(index, file);
+    }
+
+    fn planned(&mut self) {
+        // The planner also runs dry when the scan was stopped partway
+        if self.scanned.is_none() {
+            return;
+        }
+        self.planned = true;
+        let (files, bytes) = self.earlier;
+        self.save_state.total_files = Some((files + self.queued) as u64);
+        self.save_state.total_bytes = Some(bytes + self.queued_bytes);
+        self.observer.on_event(&Event::Planned {
+            files: self.queued,
+            bytes: self.queued_bytes,
+            skipped: self.skipped,
+            unchanged: self.unchanged,
+        });
+    }
+
+    fn started(&mut self, index: usize) {
+        let file = &self.files[&index];
+        self.observer.on_event(&Event::FileStarted {
+            index: index + 1,
+            source: &file.source_path,
+            target: &file.target_path,
+            size: file.size,
+        });
+    }
+
+    fn progress(&mut self, index: usize, progress: FileProgress) {
+        self.observer.on_event(&match progress {
+            FileProgress::Synced(offset) => return self.synced(index, offset),
+            FileProgress::Bytes(copied) => Event::FileProgress {
+                index: index + 1,
+                copied,
+                size: self.files[&index].size,
+            },
+            FileProgress::Retry {
+                attempt,
+                delay,
+                ref error,
+            } => Event::FileRetry {
+                index: index + 1,
+                attempt,
+                delay,
+                error,
+            },
+        });
+    }
+
+    // Renames a file being moved into place once its log entry is on disk, so
+    // a crash in between never leaves a move undo doesn't know about
+    fn rename(&mut self, index: usize, replaced: bool, written: Written) -> bool {
+        let file = Arc::clone(&self.files[&index]);
+        if let Some(journal) = self.checkpointer.journal() {
+            let logged = journal
+                .record(&Operation::Move {
+                    source: file.source_path.clone(),
+                    target: file.target_path.clone(),
+                    replaced,
+                    written,
+                })
+                .and_then(|()| journal.sync());
+            if let Err(e) = logged {
+                self.report.warn(
+                    file.target_path.clone(),
+                    format!("Could not log this file for undo: {}", e),
+                );
+            }
+        }
+        self.fs.rename(&file.source_path, &file.target_path).is_ok()
+    }
+
+    // Lists the partial file in the save as it stands, so it survives a crash
+    // or power loss and is picked up where it left off
+    fn synced(&mut self, index: usize, offset: u64) {
+        let file = &self.files[&index];
+        let Some(modified) = file.modified else {
+            return;
+        };
+        let partial = InFlightFile {
+            source: file.source_path.clone(),
+            target: file.target_path.clone(),
+            size: file.size,
+            modified,
+            offset,
+        };
+        self.save_state
+            .in_flight
+            .retain(|other| other.source != partial.source);
+        self.save_state.in_flight.push(partial);
+
+        if let Err(e) = self.checkpointer.copying(&mut self.save_state) {
+            self.report.warn(
+                self.checkpointer.save_path().unwrap_or_default(),
+                format!("Could not write checkpoint: {}", e),
+            );
+        }
+    }
+
+    fn done(&mut self, attempted: Attempted) -> Result<(), OrganizeError> {
+        let Attempted {
+            index,
+            replaced,
+            attempts,
+            retries,
+            created_dirs,
+            result,
+        } = attempted;
+        let file = Arc::clone(&self.files[&index]);
+        self.report.retries += retries;
+        // However the file ended, the partial file a checkpoint listed is gone
+        // or out of date
+        self.save_state
+            .in_flight
+            .retain(|partial| partial.source != file.source_path);
+
+        if let Some(journal) = self.checkpointer.journal() {
+            for path in created_dirs {
+                if let Err(e) = journal.record(&Operation::Mkdir { path: path.clone() }) {
+                    self.report
+                        .warn(path, format!("Could not log this folder for undo: {}", e));
+                }
+            }
+        }
+
+        let mut abort = None;
+        let ending = match result {
+            None => Ending::Unfinished,
+            Some(Err(e)) if self.options.on_error == ErrorPolicy::Abort => {
+                abort = Some(OrganizeError::File {
+                    operation: e.operation,
+                    source: file.source_path.clone(),
+                    target: file.target_path.clone(),
+                    error: e.error,
+                });
+                Ending::Unfinished
+            }
+            Some(Err(e)) => {
+                let failure = FailedFile {
+                    path: file.source_path.clone(),
+                    target: file.target_path.clone(),
+                    operation: e.operation,
+                    kind: e.error.kind(),
+                    message: e.error.to_string(),
+                    attempts,
+                };
+                self.save_state.failed_files.push(failure.clone());
+                self.settled += 1;
+                Ending::Failed(failure)
+            }
+            Some(Ok((CopyOutcome::Deferred, _, _))) => {
+                self.report.deferred.push(file.source_path.clone());
+                self.settled += 1;
+                Ending::Deferred
+            }
+            Some(Ok((CopyOutcome::Cancelled { bytes_copied }, size, modified))) => {
+                self.save_state.in_flight.push(InFlightFile {
+                    source: file.source_path.clone(),
+                    target: file.target_path.clone(),
+                    size,
+                    modified,
+                    offset: bytes_copied,
+                });
+                Ending::Unfinished
+            }
+            Some(Ok((
+                CopyOutcome::Copied {
+                    warnings,
+                    hash,
+                    verified,
+                },
+                _,
+                modified,
+            ))) => {
+                for warning in warnings {
+                    self.report.warn(file.target_path.clone(), warning);
+                }
+                if retries > 0 {
+                    self.report.warn(
+                        file.source_path.clone(),
+                        match retries {
+                            1 => "Copied after 1 retry".to_string(),
+                            _ => format!("Copied after {} retries", retries),
+                        },
+                    );
+                }
+                // Files that weren't copied were renamed, and logged before that
+                self.copied(&file, replaced, hash.clone(), modified, !verified);
+                Ending::Copied {
+                    replaced,
+                    verified,
+                    hash,
+                }
+            }
+        };
+
+        self.endings.insert(index, ending);
+        let next_ending = self.next_ending;
+        while let Some(ending) = self.endings.remove(&self.next_ending) {
+            self.report_ending(self.next_ending, ending);
+            self.next_ending += 1;
+        }
+        if self.next_ending > next_ending {
+            self.scheduler.ended(self.next_ending);
+        }
+        match abort {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    fn copied(
+        &mut self,
+        file: &OrganizedFile,
+        replaced: bool,
+        hash: Option<String>,
+        modified: SystemTime,
+        renamed: bool,
+    ) {
+        // A move's log entry has to be on disk before the original goes away
+        if !renamed && let Some(journal) = self.checkpointer.journal() {
+            let logged =
+                Written::of(self.fs, &file.target_path, hash.clone()).and_then(|written| {
+                    let source = file.source_path.clone();
+                    let target = file.target_path.clone();
+                    journal.record(&if self.moving {
+                        Operation::Move {
+                            source,
+                            target,
+                            replaced,
+                            written,
+                        }
+                    } else {
+                        Operation::Copy {
+                            source,
+                            target,
+                            replaced,
+                            written,
+                        }
+                    })?;
+                    if self.moving { journal.sync() } else { Ok(()) }
+                });
+            if let Err(e) = logged {
+                self.report.warn(
+                    file.target_path.clone(),
+                    format!("Could not log this file for undo: {}", e),
+                );
+            }
+        }
+
+        if self.moving
+            && self.fs.exists(&file.source_path)
+            && let Err(e) = self.fs.remove_file(&file.source_path)
+        {
+            self.report.warn(
+                file.source_path.clone(),
+                format!("Copied but could not remove the original: {}", e),
+            );
+        }
+
+        self.report.copied += 1;
+        self.settled += 1;
+
+        if let Some(index) = self.index {
+            let entry = IndexEntry {
+                size: file.size,
+                modified,
+                inode: file.inode,
+                hash: hash.clone(),
+                target: file.target_path.clone(),
+                mode: self.save_state.settings.mode,
+                at: SystemTime::now(),
+            };
+            if let Err(e) = index.record(&file.source_path, &entry) {
+                self.report.warn(
+                    index.path().to_path_buf(),
+                    format!(
+                        "Could not add {} to the index: {}",
+                        file.source_path.display(),
+                        e
+                    ),
+                );
+            }
+        }
+
+        // Add to save state
+        self.save_state.add_processed_file(ProcessedFile {
+            path: file.source_path.clone(),
+            name: file.file_name.clone(),
+            size: file.size,
+            modified,
+            target: Some(file.target_path.clone()),
+            hash,
+        });
+
+        if let Err(e) = self.checkpointer.file_done(&mut self.save_state) {
+            self.report.warn(
+                self.checkpointer.save_path().unwrap_or_default(),
+                format!("Could not write checkpoint: {}", e),
+            );
+        }
+    }
+
+    fn report_ending(&mut self, index: usize, ending: Ending) {
+        let Some(file) = self.files.remove(&index) else {
+            return;
+        };
+        match ending {
+            Ending::Copied {
+                replaced,
+                verified,
+                hash,
+            } => {
+                if verified {
+                    self.observer.on_event(&Event::FileVerified {
+                        index: index + 1,
+                        target: &file.target_path,
+                        hash: hash.as_deref(),
+                    });
+                }
+                self.observer.on_event(&Event::FileFinished {
+                    index: index + 1,
+                    source: &file.source_path,
+                    target: &file.target_path,
+                    size: file.size,
+                    replaced,
+                });
+            }
+            Ending::Failed(failure) => self.observer.on_event(&Event::FileFailed {
+                index: index + 1,
+                failure: &failure,
+            }),
+            Ending::Deferred => self.observer.on_event(&Event::FileDeferred {
+                index: index + 1,
+                source: &file.source_path,
+            }),
+            Ending::Unfinished => {}
+        }
+    }
+
+    // Once the workers have drained after an abort, writes the save and the run
+    // log as far as they got. The report goes nowhere, the run ends in an error.
+    fn abort(mut self) {
+        let _ = self.checkpointer.write(&mut self.save_state);
+        self.finish();
+    }
+
+    fn finish(mut self) -> RunReport {
+        // Whatever waits on a file that never finished
+        for (index, ending) in std::mem::take(&mut self.endings) {
+            self.report_ending(index, ending);
+        }
+
+        sync_journal(&mut self.checkpointer, &mut self.report);
+        if let Some(index) = self.index
+            && let Err(e) = index.flush()
+        {
+            self.report.warn(
+                index.path().to_path_buf(),
+                format!("Could not update the index: {}", e),
+            );
+        }
+        self.report.unchanged = self.unchanged;
+        self.report.failures = self.save_state.failed_files.clone();
+        if !self.planned || self.settled < self.queued {
+            self.report.interrupted = Some(self.save_state);
+        } else if !self.save_state.failed_files.is_empty() {
+            self.report.failed = Some(self.save_state);
+        }
+        self.report
+    }
+}
+
+// Everything needed to bring one file across, short of recording it. When
+// `renaming`, `rename` is asked to rename the file into place first.
+fn copy_one<F, R>(
+    file: &OrganizedFile,
+    resume: &[InFlightFile],
+    renaming: bool,
+    context: &mut CopyContext,
+    created_dirs: &mut Vec<PathBuf>,
+    progress_callback: F,
+    rename: R,
+) -> Result<(CopyOutcome, u64, SystemTime), FileError>
+where
+    F: FnMut(FileProgress),
+    R: FnOnce(Written) -> bool,
+{
+    let fs = context.fs;
+    let source_meta = fs
+        .metadata(&file.source_path)
+        .during(FileOperation::ReadSource)?;
+    let modified = source_meta.modified().unwrap_or_else(|_| SystemTime::now());
+    // Changed since the scan, or so recently that it may still be written to
+    if source_meta.len() != file.size
+        || source_meta.modified().ok() != file.modified
+        || recently_modified(&source_meta, context.options.quiet_period)
+    {
+        return Ok((CopyOutcome::Deferred, source_meta.len(), modified));
+    }
+    if let Some(parent) = file.target_path.parent() {
+        create_dirs(fs, parent, created_dirs).during(FileOperation::CreateDir)?;
+    }
+
+    let resume_offset = resume
+        .iter()
+        .find(|partial| {
+            partial.source == file.source_path
+                && partial.target == file.target_path
+                && partial.size == source_meta.len()
+                && partial.modified == modified
+        })
+        .map(|partial| partial.offset);
+
+    // Moves within one filesystem are a rename, anything else is copied. A
+    // rename keeps the size, times and content, so the log entry can describe
+    // the target before it exists.
+    if renaming {
+        let hash = context
+            .options
+            .hash
+            .then(|| hash_file(fs, &file.source_path).ok())
+            .flatten();
+        if let Ok(written) = Written::of(fs, &file.source_path, hash.clone())
+            && rename(written)
+        {
+            let renamed = CopyOutcome::Copied {
+                warnings: Vec::new(),
+                hash,
+                verified: false,
+            };
+            return Ok((renamed, source_meta.len(), modified));
+        }
+    }
+
+    let outcome = copy_file_with_progress(
+        &file.source_path,
+        &file.target_path,
+        file.size,
+        context,
+        resume_offset,
+        progress_callback,
+    )?;
+    Ok((outcome, source_meta.len(), modified))
+}
+
+// Like `create_dir_all`, but notes every folder it had to create so the run
+// log can list them for undo
+fn create_dirs(fs: &dyn FileSystem, dir: &Path, created: &mut Vec<PathBuf>) -> io::Result<()> {
+    let missing: Vec<_> = dir.ancestors().take_while(|d| !fs.exists(d)).collect();
+
+    for dir in missing.into_iter().rev() {
+        match fs.create_dir(dir) {
+            Ok(()) => created.push(dir.to_path_buf()),
+            // Another worker got there first
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+fn sync_journal(checkpointer: &mut Checkpointer, report: &mut RunReport) {
+    if let Some(journal) = checkpointer.journal()
+        && let Err(e) = journal.sync()
+    {
+        report.warn(
+            crate::runs::journal_path(journal.id()),
+            format!("Could not write the run log: {}", e),
+        );
+    }
 }
 
 fn copy_file_with_progress<F>(
-    source: &PathBuf,
-    target: &PathBuf,
-    _file_name: &str,
+    source: &Path,
+    target: &Path,
     file_size: u64,
+    context: &mut CopyContext,
+    resume_offset: Option<u64>,
+    progress_callback: F,
+) -> Result<CopyOutcome, FileError>
+where
+    F: FnMut(FileProgress),
+{
+    // Write under a hidden temporary name so a failed or killed copy never
+    // leaves a truncated file at the final path
+    let temp_path = temp_path_for(target);
+
+    let outcome = write_temp_file(
+        source,
+        &temp_path,
+        file_size,
+        context,
+        resume_offset,
+        progress_callback,
+    );
+    let copied = match outcome {
+        Ok(copied @ CopyOutcome::Copied { .. }) => copied,
+        // The partial file stays behind so the next run can append to it
+        Ok(cancelled @ CopyOutcome::Cancelled { .. }) => return Ok(cancelled),
+        Ok(CopyOutcome::Deferred) => {
+            let _ = context.fs.remove_file(&temp_path);
+            return Ok(CopyOutcome::Deferred);
+        }
+        Err(e) => {
+            let _ = context.fs.remove_file(&temp_path);
+            return Err(e);
+        }
+    };
+
+    context
+        .fs
+        .rename(&temp_path, target)
+        .inspect_err(|_| {
+            let _ = context.fs.remove_file(&temp_path);
+        })
+        .during(FileOperation::Rename)?;
+
+    Ok(copied)
+}
+
+fn write_temp_file<F>(
+    source: &Path,
+    temp_path: &Path,
+    file_size: u64,
+    context: &mut CopyContext,
+    resume_offset: Option<u64>,
     mut progress_callback: F,
-) -> Result<(), OrganizeError>
+) -> Result<CopyOutcome, FileError>
 where
-    F: FnMut(u64),
+    F: FnMut(FileProgress),
 {
-    let mut source_file =
-        File::open(source).map_err(|e| OrganizeError::FileCopyFailed(e.to_string()))?;
-    let mut target_file =
-        File::create(target).map_err(|e| OrganizeError::FileCopyFailed(e.to_string()))?;
+    let fs = context.fs;
+    let options = context.options;
+    let mut source_file = fs.open(source).during(FileOperation::ReadSource)?;
 
-    let mut buffer = [0; BUFFER_SIZE];
-    let mut bytes_copied = 0u64;
+    let mut hasher = options.hash.then(blake3::Hasher::new);
+    let mut resume_offset = resume_offset
+        .filter(|&offset| partial_matches_source(fs, &mut source_file, temp_path, offset));
+    // The hash has to vouch for the source and the target alike, so with
+    // hashing on the whole partial file is checked against the source and a
+    // copy that went bad anywhere starts over
+    if let (Some(offset), Some(hasher)) = (resume_offset, hasher.as_mut()) {
+        let mut source_prefix = blake3::Hasher::new();
+        hash_prefix(fs, source, offset, &mut source_prefix).during(FileOperation::ReadSource)?;
+        hash_prefix(fs, temp_path, offset, hasher).during(FileOperation::WriteTarget)?;
+        if hasher.finalize() != source_prefix.finalize() {
+            *hasher = blake3::Hasher::new();
+            resume_offset = None;
+        }
+    }
+    let (mut target_file, mut bytes_copied) = match resume_offset {
+        Some(offset) => open_for_append(fs, &mut source_file, temp_path, offset)
+            .during(FileOperation::WriteTarget)?,
+        None => {
+            source_file
+                .seek(SeekFrom::Start(0))
+                .during(FileOperation::ReadSource)?;
+            let target_file = fs.create(temp_path).during(FileOperation::WriteTarget)?;
+            (target_file, 0)
+        }
+    };
+
+    let started = source_file.metadata().during(FileOperation::ReadSource)?;
+    let sparse = transfer::is_sparse(&started);
+    let mut transfer = Transfer::new(options.method, hasher.is_some(), sparse);
+    progress_callback(FileProgress::Bytes(bytes_copied));
+    let mut synced = Instant::now();
 
     loop {
-        let bytes_read = source_file
-            .read(&mut buffer)
-            .map_err(|e| OrganizeError::FileCopyFailed(e.to_string()))?;
+        if context.stop_signal.load(Ordering::SeqCst) {
+            // Only bytes that reached the disk count as resumable
+            target_file.sync_data().during(FileOperation::WriteTarget)?;
+            return Ok(CopyOutcome::Cancelled { bytes_copied });
+        }
+
+        let step = match transfer.copy_chunk(
+            source_file.as_mut(),
+            target_file.as_mut(),
+            bytes_copied,
+            file_size,
+            hasher.as_mut(),
+        ) {
+            Ok(step) => step,
+            Err(e) => {
+                let Some(delay) = context.backoff.next_delay(&e.error) else {
+                    return Err(e);
+                };
+                progress_callback(FileProgress::Retry {
+                    attempt: context.backoff.attempts(),
+                    delay,
+                    error: e.error,
+                });
+                if !retry::wait(delay, context.stop_signal) {
+                    // The handle may be dead, resume checks the partial file anyway
+                    let _ = target_file.sync_data();
+                    return Ok(CopyOutcome::Cancelled { bytes_copied });
+                }
+
+                // Fresh handles, picking up at the last chunk that was written whole
+                source_file = fs.open(source).during(FileOperation::ReadSource)?;
+                (target_file, bytes_copied) =
+                    open_for_append(fs, &mut source_file, temp_path, bytes_copied)
+                        .during(FileOperation::WriteTarget)?;
+                continue;
+            }
+        };
 
-        if bytes_read == 0 {
+        if step == 0 {
             // Make sure to call progress one last time with total size
-            progress_callback(file_size);
+            progress_callback(FileProgress::Bytes(file_size));
             break;
         }
 
-        target_file
-            .write_all(&buffer[..bytes_read])
-            .map_err(|e| OrganizeError::FileCopyFailed(e.to_string()))?;
+        bytes_copied += step;
+        progress_callback(FileProgress::Bytes(bytes_copied));
 
-        bytes_copied += bytes_read as u64;
-        progress_callback(bytes_copied);
+        // A save only lists a partial file as far as it is known to be on
+        // disk, so flush it every so often for the next checkpoint
+        if synced.elapsed() >= options.checkpoint.interval {
+            target_file.sync_data().during(FileOperation::WriteTarget)?;
+            progress_callback(FileProgress::Synced(bytes_copied));
+            synced = Instant::now();
+        }
     }
 
-    Ok(())
+    let source_meta = source_file.metadata().during(FileOperation::ReadSource)?;
+    if bytes_copied != source_meta.len() || source_meta.modified().ok() != started.modified().ok() {
+        return Ok(CopyOutcome::Deferred);
+    }
+    // Only files on disk carry permissions and the like
+    let warnings = match (source_meta.raw(), target_file.as_file()) {
+        (Some(raw), Some(file)) => preserve_metadata(source, raw, file, &options.preserve),
+        _ => Vec::new(),
+    };
+
+    if options.fsync {
+        target_file.sync_all().during(FileOperation::WriteTarget)?;
+    }
+
+    Ok(CopyOutcome::Copied {
+        warnings,
+        hash: hasher.map(|hasher| hasher.finalize().to_hex().to_string()),
+        verified: true,
+    })
+}
+
+// Checks that the partial file holds at least `offset` bytes and that the last
+// stretch before the offset still matches the source
+fn partial_matches_source(
+    fs: &dyn FileSystem,
+    source_file: &mut Box<dyn ReadHandle>,
+    temp_path: &Path,
+    offset: u64,
+) -> bool {
+    let Ok(mut partial) = fs.open(temp_path) else {
+        return false;
+    };
+    if partial.metadata().map(|m| m.len()).unwrap_or(0) < offset {
+        return false;
+    }
+
+    let window = offset.min(RESUME_VERIFY_SIZE);
+    let start = offset - window;
+    let mut expected = vec![0; window as usize];
+    let mut actual = vec![0; window as usize];
+
+    let read_at = |file: &mut dyn ReadHandle, buf: &mut [u8]| {
+        file.seek(SeekFrom::Start(start))
+            .and_then(|_| file.read_exact(buf))
+            .is_ok()
+    };
+
+    read_at(source_file.as_mut(), &mut expected)
+        && read_at(partial.as_mut(), &mut actual)
+        && expected == actual
+}
+
+fn open_for_append(
+    fs: &dyn FileSystem,
+    source_file: &mut Box<dyn ReadHandle>,
+    temp_path: &Path,
+    offset: u64,
+) -> io::Result<(Box<dyn WriteHandle>, u64)> {
+    let mut target_file = fs.open_write(temp_path)?;
+    // Anything past the offset was never confirmed on disk
+    target_file.set_len(offset)?;
+    target_file.seek(SeekFrom::Start(offset))?;
+    source_file.seek(SeekFrom::Start(offset))?;
+
+    Ok((target_file, offset))
 }
@@ -0,0 +1,80 @@
+use crate::models::RetryOptions;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+const MAX_DELAY: Duration = Duration::from_secs(30);
+
+// Errors network file systems hand out for hiccups that usually clear up by
+// themselves, as opposed to ones that will fail the same way every time
+pub fn is_transient(error: &io::Error) -> bool {
+    use io::ErrorKind::*;
+
+    if matches!(
+        error.kind(),
+        WouldBlock
+            | TimedOut
+            | Interrupted
+            | StaleNetworkFileHandle
+            | ConnectionReset
+            | ConnectionAborted
+            | NotConnected
+    ) {
+        return true;
+    }
+
+    #[cfg(unix)]
+    if let Some(code) = error.raw_os_error() {
+        return matches!(
+            code,
+            libc::EAGAIN | libc::ETIMEDOUT | libc::EIO | libc::ESTALE
+        );
+    }
+    false
+}
+
+// Exponential backoff for one file, shared by every step of copying it
+pub struct Backoff {
+    options: RetryOptions,
+    attempts: u32,
+}
+
+impl Backoff {
+    pub fn new(options: &RetryOptions) -> Self {
+        Self {
+            options: options.clone(),
+            attempts: 0,
+        }
+    }
+
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    // How long to wait before trying again, or None when the error is not
+    // worth retrying or the file is out of attempts
+    pub fn next_delay(&mut self, error: &io::Error) -> Option<Duration> {
+        if !is_transient(error) || self.attempts >= self.options.limit {
+            return None;
+        }
+
+        let delay = self
+            .options
+            .base_delay
+            .saturating_mul(1 << self.attempts.min(16));
+        self.attempts += 1;
+        Some(delay.min(MAX_DELAY))
+    }
+}
+
+// Sleeps for `delay` unless the run is stopped first. Returns false if it was.
+pub fn wait(delay: Duration, stop_signal: &AtomicBool) -> bool {
+    let until = Instant::now() + delay;
+    while Instant::now() < until {
+        if stop_signal.load(Ordering::SeqCst) {
+            return false;
+        }
+        std::thread::sleep(Duration::from_millis(50).min(until - Instant::now()));
+    }
+    !stop_signal.load(Ordering::SeqCst)
+}
@@ -1,12 +1,56 @@
 use crate::models::CustomFile;
-use std::path::PathBuf;
-use walkdir::WalkDir;
-
-pub fn get_all_files(input_path: &PathBuf) -> Vec<CustomFile> {
-    WalkDir::new(input_path)
-        .into_iter()
-        .filter_map(|entry| entry.ok())
-        .filter(|entry| entry.file_type().is_file())
-        .filter_map(|entry| CustomFile::from_path(&entry.path().to_path_buf()))
-        .collect()
-}
\ No newline at end of file
+use crate::vfs::{FileMeta, FileSystem};
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+// Extensions browsers and download tools give files they are still writing
+const PARTIAL_DOWNLOADS: &[&str] = &["part", "crdownload", "download", "tmp"];
+
+// Files below `input_path` as the walk finds them, reading folders on
+// `threads` threads. Downloads still in progress are left for a later run.
+pub fn scan_files<'a>(
+    fs: &'a dyn FileSystem,
+    input_path: &Path,
+    threads: usize,
+) -> impl Iterator<Item = CustomFile> + Send + use<'a> {
+    let root = input_path.to_path_buf();
+    fs.walk_files(input_path, threads)
+        .filter(move |(path, _)| !in_partial_download(&root, path))
+        .filter_map(|(path, meta)| CustomFile::from_path(&path, meta))
+}
+
+pub fn get_all_files(fs: &dyn FileSystem, input_path: &Path, threads: usize) -> Vec<CustomFile> {
+    scan_files(fs, input_path, threads).collect()
+}
+
+// Whether `path` is a download in progress or lies in one, like the files of
+// a Safari `.download` bundle. Only the part below `root` is looked at.
+pub fn in_partial_download(root: &Path, path: &Path) -> bool {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .iter()
+        .any(|name| is_partial_download(Path::new(name)))
+}
+
+pub fn is_partial_download(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            PARTIAL_DOWNLOADS
+                .iter()
+                .any(|partial| ext.eq_ignore_ascii_case(partial))
+        })
+}
+
+// Files modified this recently may still be being written
+pub fn recently_modified(meta: &FileMeta, quiet_period: Duration) -> bool {
+    let Ok(modified) = meta.modified() else {
+        return false;
+    };
+    // Either way round, as the clocks of network shares drift
+    let age = match SystemTime::now().duration_since(modified) {
+        Ok(age) => age,
+        Err(e) => e.duration(),
+    };
+    age < quiet_period
+}
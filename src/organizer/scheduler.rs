@@ -0,0 +1,227 @@
+use crate::models::{ConcurrencyOptions, OrganizedFile};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::Duration;
+
+// Planned files waiting for a worker. The planner waits when this many are
+// queued, so a huge tree doesn't get planned far ahead of the copy.
+const CAPACITY: usize = 1024;
+// How many queued files are looked at for one whose devices are free, so a
+// long queue on a busy disk doesn't get scanned on every pick
+const LOOKAHEAD: usize = 256;
+// How far past the oldest file that hasn't ended a worker may start. Files
+// that end early wait to be reported in plan order, so this bounds how many
+// are kept while one large file copies.
+const AHEAD: usize = 1024;
+
+// Hands the planned files to the copy workers in plan order as the planner
+// queues them, holding a file back while its source or destination device has
+// as many copies running as it allows, or while another file is being copied
+// to the same target
+pub struct Scheduler {
+    queue: Mutex<Queue>,
+    // Notified whenever a file is queued or taken, a copy finishes or the
+    // planner is done
+    changed: Condvar,
+    destination: Option<u64>,
+    options: ConcurrencyOptions,
+}
+
+struct Queue {
+    pending: VecDeque<Pending>,
+    busy: HashMap<u64, usize>,
+    limits: HashMap<u64, usize>,
+    // Targets being copied to. Two files that overwrite the same target would
+    // write the same temporary file.
+    targets: HashSet<PathBuf>,
+    // Every file before this one has ended
+    ended: usize,
+    // Set once the planner has queued its last file
+    closed: bool,
+}
+
+struct Pending {
+    index: usize,
+    file: Arc<OrganizedFile>,
+    // The devices the copy reads from and writes to
+    devices: Vec<u64>,
+}
+
+// A file a worker is copying. Its devices and target are freed up when it is
+// dropped.
+pub struct Slot<'a> {
+    pub index: usize,
+    pub file: Arc<OrganizedFile>,
+    devices: Vec<u64>,
+    scheduler: &'a Scheduler,
+}
+
+impl Scheduler {
+    pub fn new(destination: Option<u64>, options: &ConcurrencyOptions) -> Self {
+        Self {
+            queue: Mutex::new(Queue {
+                pending: VecDeque::new(),
+                busy: HashMap::new(),
+                limits: HashMap::new(),
+                targets: HashSet::new(),
+                ended: 0,
+                closed: false,
+            }),
+            changed: Condvar::new(),
+            destination,
+            options: options.clone(),
+        }
+    }
+
+    // Queues the next planned file, waiting while the queue is full. False
+    // when the run was stopped instead.
+    pub fn push(&self, index: usize, file: Arc<OrganizedFile>, stop_signal: &AtomicBool) -> bool {
+        let mut devices: Vec<u64> = file.device.into_iter().chain(self.destination).collect();
+        devices.dedup();
+
+        let mut queue = self.lock();
+        while queue.pending.len() >= CAPACITY {
+            if stop_signal.load(Ordering::SeqCst) {
+                return false;
+            }
+            queue = self.wait(queue);
+        }
+        for &device in &devices {
+            queue
+                .limits
+                .entry(device)
+                .or_insert_with(|| device_limit(device, &self.options));
+        }
+        queue.pending.push_back(Pending {
+            index,
+            file,
+            devices,
+        });
+        drop(queue);
+        self.changed.notify_all();
+        true
+    }
+
+    // No more files are coming, so the workers can finish once the queue is empty
+    pub fn close(&self) {
+        self.lock().closed = true;
+        self.changed.notify_all();
+    }
+
+    // Every file before `next` has ended, so files further on may start
+    pub fn ended(&self, next: usize) {
+        self.lock().ended = next;
+        self.changed.notify_all();
+    }
+
+    // The next file to copy, waiting while every queued file is held back or
+    // the planner hasn't caught up. None once the planner is done and the
+    // queue is empty, or the run was stopped.
+    pub fn next(&self, stop_signal: &AtomicBool) -> Option<Slot<'_>> {
+        let mut queue = self.lock();
+        loop {
+            if stop_signal.load(Ordering::SeqCst) || (queue.closed && queue.pending.is_empty()) {
+                return None;
+            }
+            if let Some(pending) = queue.take_ready() {
+                drop(queue);
+                // The planner may be waiting for room
+                self.changed.notify_all();
+                return Some(Slot {
+                    index: pending.index,
+                    file: pending.file,
+                    devices: pending.devices,
+                    scheduler: self,
+                });
+            }
+            queue = self.wait(queue);
+        }
+    }
+
+    // Woken by any change, and every so often to notice a stop
+    fn wait<'a>(&self, queue: MutexGuard<'a, Queue>) -> MutexGuard<'a, Queue> {
+        self.changed
+            .wait_timeout(queue, Duration::from_millis(100))
+            .map(|(queue, _)| queue)
+            .unwrap_or_else(|e| e.into_inner().0)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Queue> {
+        self.queue.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Queue {
+    fn take_ready(&mut self) -> Option<Pending> {
+        let position = self.pending.iter().take(LOOKAHEAD).position(|pending| {
+            pending.index < self.ended + AHEAD
+                && !self.targets.contains(&pending.file.target_path)
+                && pending
+                    .devices
+                    .iter()
+                    .all(|device| self.busy.get(device).copied().unwrap_or(0) < self.limits[device])
+        })?;
+
+        let pending = self.pending.remove(position)?;
+        for &device in &pending.devices {
+            *self.busy.entry(device).or_default() += 1;
+        }
+        self.targets.insert(pending.file.target_path.clone());
+        Some(pending)
+    }
+}
+
+impl Drop for Slot<'_> {
+    fn drop(&mut self) {
+        let mut queue = self.scheduler.lock();
+        for device in &self.devices {
+            if let Some(busy) = queue.busy.get_mut(device) {
+                *busy -= 1;
+            }
+        }
+        queue.targets.remove(&self.file.target_path);
+        drop(queue);
+        self.scheduler.changed.notify_all();
+    }
+}
+
+// Never below one, a device that allows no copies would hold its files back
+// forever
+fn device_limit(device: u64, options: &ConcurrencyOptions) -> usize {
+    options
+        .per_device
+        .unwrap_or_else(|| {
+            if is_rotational(device) {
+                1
+            } else {
+                options.jobs
+            }
+        })
+        .max(1)
+}
+
+// Spinning disks get slower, not faster, when the heads have to seek between
+// several files
+#[cfg(target_os = "linux")]
+fn is_rotational(device: u64) -> bool {
+    let block = std::path::PathBuf::from(format!(
+        "/sys/dev/block/{}:{}",
+        libc::major(device),
+        libc::minor(device)
+    ));
+    // Partitions keep the queue settings on their parent disk
+    [
+        block.join("queue/rotational"),
+        block.join("../queue/rotational"),
+    ]
+    .iter()
+    .find_map(|path| std::fs::read_to_string(path).ok())
+    .is_some_and(|value| value.trim() == "1")
+}
+
+#[cfg(not(target_os = "linux"))]
+fn is_rotational(_device: u64) -> bool {
+    false
+}
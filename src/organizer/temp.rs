@@ -0,0 +1,42 @@
+use crate::models::SaveState;
+use crate::vfs::FileSystem;
+use std::path::{Path, PathBuf};
+
+const TEMP_PREFIX: &str = ".";
+const TEMP_SUFFIX: &str = ".forg-partial";
+
+pub fn temp_path_for(target: &Path) -> PathBuf {
+    let file_name = target
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    target.with_file_name(format!("{}{}{}", TEMP_PREFIX, file_name, TEMP_SUFFIX))
+}
+
+pub fn is_temp_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|n| n.starts_with(TEMP_PREFIX) && n.ends_with(TEMP_SUFFIX))
+}
+
+// Removes partial files left behind by a run that was killed mid-copy, except
+// the ones a save file still points at
+pub fn remove_stale_temp_files(fs: &dyn FileSystem, output_path: &Path, keep: &[PathBuf]) -> usize {
+    fs.walk_files(output_path, 1)
+        .map(|(path, _)| path)
+        .filter(|path| is_temp_file(path) && !keep.contains(path))
+        .filter(|path| fs.remove_file(path).is_ok())
+        .count()
+}
+
+// Partial files that some save can still pick up where its copy stopped
+pub fn resumable_temp_files(fs: &dyn FileSystem) -> Vec<PathBuf> {
+    SaveState::list_saves(fs)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|path| SaveState::load(fs, path).ok())
+        .flat_map(|save| save.in_flight)
+        .map(|partial| temp_path_for(&partial.target))
+        .collect()
+}
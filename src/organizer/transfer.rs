@@ -0,0 +1,309 @@
+use crate::models::{CopyMethod, FileOperation};
+use crate::vfs::{FileMeta, ReadHandle, WriteHandle};
+use std::fs::File;
+use std::io::{self, SeekFrom};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+// Chunks double while they finish quickly and halve when they don't, so fast
+// disks take few large steps and slow shares still report progress and notice
+// a stop every so often
+const MIN_CHUNK: usize = 64 * 1024;
+const START_CHUNK: usize = 256 * 1024;
+const MAX_CHUNK: usize = 8 * 1024 * 1024;
+const FAST_CHUNK: Duration = Duration::from_millis(20);
+const SLOW_CHUNK: Duration = Duration::from_millis(200);
+
+pub struct FileError {
+    pub operation: FileOperation,
+    pub error: io::Error,
+}
+
+pub trait During<T> {
+    fn during(self, operation: FileOperation) -> Result<T, FileError>;
+}
+
+impl<T> During<T> for io::Result<T> {
+    fn during(self, operation: FileOperation) -> Result<T, FileError> {
+        self.map_err(|error| FileError { operation, error })
+    }
+}
+
+// Moves a file's bytes chunk by chunk, in the kernel where the platform and
+// both file systems allow it
+pub struct Transfer {
+    method: CopyMethod,
+    // Whether an unsupported method may give way to the next best one
+    fallback: bool,
+    // Holes in the source are skipped and left as holes in the target
+    sparse: bool,
+    chunk: usize,
+    buffer: Vec<u8>,
+}
+
+impl Transfer {
+    pub fn new(method: CopyMethod, hashing: bool, sparse: bool) -> Self {
+        // The hash needs every byte in memory, which only the buffered copy has
+        let (method, fallback) = match method {
+            _ if hashing => (CopyMethod::Buffered, false),
+            CopyMethod::Auto if cfg!(target_os = "linux") => (CopyMethod::CopyFileRange, true),
+            CopyMethod::Auto => (CopyMethod::Buffered, false),
+            method => (method, false),
+        };
+
+        Self {
+            method,
+            fallback,
+            sparse,
+            chunk: START_CHUNK,
+            buffer: Vec::new(),
+        }
+    }
+
+    // Copies the next chunk from the source's position to the target's,
+    // returning how far both moved on. 0 means `size` was reached.
+    pub fn copy_chunk(
+        &mut self,
+        source: &mut dyn ReadHandle,
+        target: &mut dyn WriteHandle,
+        offset: u64,
+        size: u64,
+        hasher: Option<&mut blake3::Hasher>,
+    ) -> Result<u64, FileError> {
+        let mut end = size;
+        if self.sparse {
+            match next_data(source, offset).during(FileOperation::ReadSource)? {
+                Some((start, _)) if start > offset => {
+                    return skip_hole(target, offset, start, hasher);
+                }
+                Some((_, data_end)) => end = data_end.min(size),
+                None if offset < size => {
+                    target.set_len(size).during(FileOperation::WriteTarget)?;
+                    return skip_hole(target, offset, size, hasher);
+                }
+                None => return Ok(0),
+            }
+        }
+
+        let len = (self.chunk as u64).min(end.saturating_sub(offset)) as usize;
+        if len == 0 {
+            return Ok(0);
+        }
+
+        let started = Instant::now();
+        let result = self.copy_range(source, target, len, hasher);
+        // FUSE and some network file systems copy nothing in the kernel short
+        // of the end instead of failing, which means the same
+        let unsupported = match &result {
+            Ok(copied) => *copied == 0,
+            Err(e) => is_unsupported(&e.error),
+        };
+        if self.fallback && unsupported {
+            self.method = match self.method {
+                CopyMethod::CopyFileRange => CopyMethod::Sendfile,
+                _ => CopyMethod::Buffered,
+            };
+            self.fallback = self.method != CopyMethod::Buffered;
+            // Nothing was copied, so the same chunk is tried the next way
+            return self.copy_chunk(source, target, offset, size, None);
+        }
+        let copied = result?;
+
+        let elapsed = started.elapsed();
+        if copied == len && elapsed < FAST_CHUNK {
+            self.chunk = (self.chunk * 2).min(MAX_CHUNK);
+        } else if elapsed > SLOW_CHUNK {
+            self.chunk = (self.chunk / 2).max(MIN_CHUNK);
+        }
+        Ok(copied as u64)
+    }
+
+    fn copy_range(
+        &mut self,
+        source: &mut dyn ReadHandle,
+        target: &mut dyn WriteHandle,
+        len: usize,
+        hasher: Option<&mut blake3::Hasher>,
+    ) -> Result<usize, FileError> {
+        match self.method {
+            CopyMethod::CopyFileRange | CopyMethod::Sendfile => {
+                let (Some(source), Some(target)) = (source.as_file(), target.as_file()) else {
+                    return Err(unsupported(
+                        "only files on disk can be copied in the kernel",
+                    ))
+                    .during(FileOperation::WriteTarget);
+                };
+                // The kernel doesn't say which side failed, and it's rarely the source
+                kernel_copy(self.method, source, target, len).during(FileOperation::WriteTarget)
+            }
+            CopyMethod::Buffered | CopyMethod::Auto => {
+                if self.buffer.len() < len {
+                    self.buffer.resize(len, 0);
+                }
+                let buffer = &mut self.buffer[..len];
+                let read = source.read(buffer).during(FileOperation::ReadSource)?;
+                target
+                    .write_all(&buffer[..read])
+                    .during(FileOperation::WriteTarget)?;
+                // Only once written, a chunk that failed is copied again from
+                // the same offset and must not be hashed twice
+                if let Some(hasher) = hasher {
+                    hasher.update(&buffer[..read]);
+                }
+                Ok(read)
+            }
+        }
+    }
+}
+
+// Copies a whole file the given way without falling back, for comparing methods
+pub fn copy_file_with(method: CopyMethod, source: &Path, target: &Path) -> io::Result<u64> {
+    let mut source_file = File::open(source)?;
+    let mut target_file = File::create(target)?;
+    let size = source_file.metadata()?.len();
+    let mut transfer = Transfer::new(method, false, false);
+    transfer.fallback = false;
+
+    let mut copied = 0;
+    loop {
+        let step = transfer
+            .copy_chunk(&mut source_file, &mut target_file, copied, size, None)
+            .map_err(|e| e.error)?;
+        if step == 0 {
+            break;
+        }
+        copied += step;
+    }
+    target_file.sync_all()?;
+    Ok(copied)
+}
+
+// Fewer blocks on disk than the length needs means the file has holes
+#[cfg(target_os = "linux")]
+pub fn is_sparse(meta: &FileMeta) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    meta.raw()
+        .is_some_and(|raw| raw.blocks().saturating_mul(512) < raw.len())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn is_sparse(_meta: &FileMeta) -> bool {
+    false
+}
+
+fn skip_hole(
+    target: &mut dyn WriteHandle,
+    from: u64,
+    to: u64,
+    hasher: Option<&mut blake3::Hasher>,
+) -> Result<u64, FileError> {
+    target
+        .seek(SeekFrom::Start(to))
+        .during(FileOperation::WriteTarget)?;
+    if let Some(hasher) = hasher {
+        let zeros = [0; 64 * 1024];
+        let mut left = to - from;
+        while left > 0 {
+            let step = left.min(zeros.len() as u64) as usize;
+            hasher.update(&zeros[..step]);
+            left -= step as u64;
+        }
+    }
+    Ok(to - from)
+}
+
+// The run of data starting at or after `offset`, or None when only a hole is
+// left. Leaves the source positioned at the start of the data.
+#[cfg(target_os = "linux")]
+fn next_data(source: &mut dyn ReadHandle, offset: u64) -> io::Result<Option<(u64, u64)>> {
+    use std::os::fd::AsRawFd;
+
+    let Some(fd) = source.as_file().map(|file| file.as_raw_fd()) else {
+        return Ok(Some((offset, u64::MAX)));
+    };
+    let seek = |from: u64, whence| {
+        // SAFETY: lseek only moves the position of a descriptor we hold open
+        match unsafe { libc::lseek(fd, from as libc::off_t, whence) } {
+            -1 => Err(io::Error::last_os_error()),
+            position => Ok(position as u64),
+        }
+    };
+
+    let start = match seek(offset, libc::SEEK_DATA) {
+        Ok(start) => start,
+        Err(e) if e.raw_os_error() == Some(libc::ENXIO) => return Ok(None),
+        // The file system can't tell holes apart, so treat it all as data
+        Err(e) if e.raw_os_error() == Some(libc::EINVAL) => {
+            source.seek(SeekFrom::Start(offset))?;
+            return Ok(Some((offset, u64::MAX)));
+        }
+        Err(e) => return Err(e),
+    };
+    let end = seek(start, libc::SEEK_HOLE)?;
+    source.seek(SeekFrom::Start(start))?;
+    Ok(Some((start, end)))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn next_data(_source: &mut dyn ReadHandle, offset: u64) -> io::Result<Option<(u64, u64)>> {
+    Ok(Some((offset, u64::MAX)))
+}
+
+#[cfg(target_os = "linux")]
+fn kernel_copy(method: CopyMethod, source: &File, target: &File, len: usize) -> io::Result<usize> {
+    use std::os::fd::AsRawFd;
+    use std::ptr;
+
+    let (source, target) = (source.as_raw_fd(), target.as_raw_fd());
+    // SAFETY: both descriptors are open for as long as the borrowed files, and
+    // null offsets make the calls use and move the files' own positions
+    let copied = unsafe {
+        match method {
+            CopyMethod::CopyFileRange => {
+                libc::copy_file_range(source, ptr::null_mut(), target, ptr::null_mut(), len, 0)
+            }
+            _ => libc::sendfile(target, source, ptr::null_mut(), len),
+        }
+    };
+    match copied {
+        -1 => Err(io::Error::last_os_error()),
+        copied => Ok(copied as usize),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn kernel_copy(
+    method: CopyMethod,
+    _source: &File,
+    _target: &File,
+    _len: usize,
+) -> io::Result<usize> {
+    Err(unsupported(&format!(
+        "{:?} is only available on Linux",
+        method
+    )))
+}
+
+// Errors meaning this way of copying doesn't work for these files, rather than
+// that the copy failed
+fn is_unsupported(error: &io::Error) -> bool {
+    if error.kind() == io::ErrorKind::Unsupported {
+        return true;
+    }
+    #[cfg(target_os = "linux")]
+    if let Some(code) = error.raw_os_error() {
+        return [
+            libc::ENOSYS,
+            libc::EXDEV,
+            libc::EINVAL,
+            libc::EOPNOTSUPP,
+            libc::EBADF,
+        ]
+        .contains(&code);
+    }
+    false
+}
+
+fn unsupported(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, reason.to_string())
+}
@@ -0,0 +1,149 @@
+use super::hash::hash_file;
+use crate::models::{CustomFile, Mode, ProcessedFile, SaveState};
+use crate::vfs::FileSystem;
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy)]
+pub enum ChangeReason {
+    SourceModified,
+    SourceHashMismatch,
+    OutputMissing,
+    OutputModified,
+    OutputHashMismatch,
+}
+
+impl std::fmt::Display for ChangeReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::SourceModified => write!(f, "source size or date changed"),
+            Self::SourceHashMismatch => write!(f, "source content changed"),
+            Self::OutputMissing => write!(f, "output file is missing"),
+            Self::OutputModified => write!(f, "output file size changed"),
+            Self::OutputHashMismatch => write!(f, "output content changed"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ChangedFile {
+    pub path: PathBuf,
+    pub reason: ChangeReason,
+}
+
+#[derive(Debug, Default)]
+pub struct ResumeDiff {
+    pub changed: Vec<ChangedFile>,
+    pub vanished: Vec<PathBuf>,
+    pub new: Vec<PathBuf>,
+}
+
+impl ResumeDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changed.is_empty() && self.vanished.is_empty() && self.new.is_empty()
+    }
+}
+
+// Compares what a save file recorded as done with what is on disk now
+pub fn diff_against_save(
+    fs: &dyn FileSystem,
+    save_state: &SaveState,
+    files: &[CustomFile],
+    verify_hash: bool,
+) -> ResumeDiff {
+    let current: HashMap<&Path, &CustomFile> =
+        files.iter().map(|f| (f.path.as_path(), f)).collect();
+    // Moved files are supposed to be gone from the input
+    let moved = save_state.settings.mode == Mode::Move;
+
+    let checks: Vec<_> = save_state
+        .processed_files
+        .par_iter()
+        .filter_map(|entry| {
+            let reason = match current.get(entry.path.as_path()) {
+                Some(file) => check_source(fs, entry, file, verify_hash)
+                    .or_else(|| check_output(fs, entry, verify_hash)),
+                None if moved => check_output(fs, entry, verify_hash),
+                None => None,
+            };
+            reason.map(|reason| ChangedFile {
+                path: entry.path.clone(),
+                reason,
+            })
+        })
+        .collect();
+
+    let processed: HashSet<&Path> = save_state
+        .processed_files
+        .iter()
+        .map(|f| f.path.as_path())
+        .collect();
+
+    let vanished = save_state
+        .processed_files
+        .iter()
+        .filter(|entry| !moved && !current.contains_key(entry.path.as_path()))
+        .map(|entry| entry.path.clone())
+        .collect();
+
+    // Files that showed up after the save was written, as opposed to ones the
+    // interrupted run simply had not reached yet
+    let new = match save_state.saved_at {
+        Some(saved_at) => files
+            .iter()
+            .filter(|f| !processed.contains(f.path.as_path()))
+            .filter(|f| {
+                f.created
+                    .max(f.modified)
+                    .is_some_and(|time| time > saved_at)
+            })
+            .map(|f| f.path.clone())
+            .collect(),
+        None => Vec::new(),
+    };
+
+    ResumeDiff {
+        changed: checks,
+        vanished,
+        new,
+    }
+}
+
+fn check_source(
+    fs: &dyn FileSystem,
+    entry: &ProcessedFile,
+    file: &CustomFile,
+    verify_hash: bool,
+) -> Option<ChangeReason> {
+    if file.size != entry.size || file.modified != Some(entry.modified) {
+        return Some(ChangeReason::SourceModified);
+    }
+
+    let expected = entry.hash.as_ref().filter(|_| verify_hash)?;
+    if hash_file(fs, &entry.path).ok().as_ref() != Some(expected) {
+        return Some(ChangeReason::SourceHashMismatch);
+    }
+
+    None
+}
+
+fn check_output(
+    fs: &dyn FileSystem,
+    entry: &ProcessedFile,
+    verify_hash: bool,
+) -> Option<ChangeReason> {
+    let target = entry.target.as_ref()?;
+    match fs.metadata(target) {
+        Err(_) => return Some(ChangeReason::OutputMissing),
+        Ok(meta) if meta.len() != entry.size => return Some(ChangeReason::OutputModified),
+        Ok(_) => {}
+    }
+
+    let expected = entry.hash.as_ref().filter(|_| verify_hash)?;
+    if hash_file(fs, target).ok().as_ref() != Some(expected) {
+        return Some(ChangeReason::OutputHashMismatch);
+    }
+
+    None
+}
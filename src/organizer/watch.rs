@@ -0,0 +1,119 @@
+use super::scanner::in_partial_download;
+use super::temp::is_temp_file;
+use crate::models::CustomFile;
+use crate::vfs::{FileMeta, FileSystem};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+// How a waiting file looked when it last changed
+struct Waiting {
+    size: u64,
+    modified: Option<SystemTime>,
+    since: Instant,
+}
+
+// Holds back files that are still being written until they have kept the same
+// size and modification time for `settle`, so bursts of changes to one file
+// end up as a single copy of the finished file
+pub struct Settler {
+    settle: Duration,
+    // The watched folder
+    source: PathBuf,
+    // The output folder, never waited on when it lies inside the input
+    output_path: PathBuf,
+    waiting: HashMap<PathBuf, Waiting>,
+}
+
+impl Settler {
+    pub fn new(settle: Duration, source: &Path, output_path: &Path) -> Self {
+        Self {
+            settle,
+            source: source.to_path_buf(),
+            output_path: output_path.to_path_buf(),
+            waiting: HashMap::new(),
+        }
+    }
+
+    // Notes that something changed at `path`. A folder that appeared brings
+    // all the files in it. Returns the files that started waiting.
+    pub fn changed(&mut self, fs: &dyn FileSystem, path: &Path) -> Vec<PathBuf> {
+        if path.starts_with(&self.output_path) {
+            return Vec::new();
+        }
+        let meta = match fs.metadata(path) {
+            Ok(meta) => meta,
+            Err(_) => {
+                self.forget(path);
+                return Vec::new();
+            }
+        };
+        if meta.is_dir() {
+            return fs
+                .walk_files(path, 1)
+                .filter(|(path, meta)| self.wait_for(path, meta))
+                .map(|(path, _)| path)
+                .collect();
+        }
+
+        if self.wait_for(path, &meta) {
+            vec![path.to_path_buf()]
+        } else {
+            Vec::new()
+        }
+    }
+
+    pub fn forget(&mut self, path: &Path) {
+        self.waiting.retain(|waiting, _| !waiting.starts_with(path));
+    }
+
+    pub fn waiting(&self) -> usize {
+        self.waiting.len()
+    }
+
+    // Files that have stopped changing, in path order. They no longer wait.
+    pub fn settled(&mut self, fs: &dyn FileSystem) -> Vec<CustomFile> {
+        let now = Instant::now();
+        let mut settled = Vec::new();
+        self.waiting.retain(|path, waiting| {
+            if now.duration_since(waiting.since) < self.settle {
+                return true;
+            }
+            // Gone, or renamed to a name that brings its own event
+            let Ok(meta) = fs.metadata(path) else {
+                return false;
+            };
+            if meta.len() != waiting.size || meta.modified().ok() != waiting.modified {
+                waiting.size = meta.len();
+                waiting.modified = meta.modified().ok();
+                waiting.since = now;
+                return true;
+            }
+            settled.extend(CustomFile::from_path(path, meta));
+            false
+        });
+        settled.sort_by(|a, b| a.path.cmp(&b.path));
+        settled
+    }
+
+    // Returns whether the file is new to the waiting list
+    fn wait_for(&mut self, path: &Path, meta: &FileMeta) -> bool {
+        if !meta.is_file()
+            || path.starts_with(&self.output_path)
+            || in_partial_download(&self.source, path)
+            || is_temp_file(path)
+        {
+            return false;
+        }
+        self.waiting
+            .insert(
+                path.to_path_buf(),
+                Waiting {
+                    size: meta.len(),
+                    modified: meta.modified().ok(),
+                    since: Instant::now(),
+                },
+            )
+            .is_none()
+    }
+}
@@ -0,0 +1,164 @@
+pub mod undo;
+
+use crate::models::Mode;
+use crate::utils::{ensure_runs_dir, get_runs_dir};
+use crate::vfs::{FileSystem, WriteHandle};
+use serde::{Deserialize, Serialize};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+// What a finished output file looked like right after the run wrote it, so
+// undo can tell whether anything touched it since
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Written {
+    pub size: u64,
+    pub modified: SystemTime,
+    pub hash: Option<String>,
+}
+
+impl Written {
+    pub fn of(fs: &dyn FileSystem, path: &Path, hash: Option<String>) -> io::Result<Self> {
+        let meta = fs.metadata(path)?;
+        Ok(Self {
+            size: meta.len(),
+            modified: meta.modified()?,
+            hash,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Operation {
+    Start {
+        input: PathBuf,
+        output: PathBuf,
+        mode: Mode,
+        at: SystemTime,
+    },
+    Mkdir {
+        path: PathBuf,
+    },
+    // `replaced` is set when the copy overwrote a file that was already there
+    Copy {
+        source: PathBuf,
+        target: PathBuf,
+        replaced: bool,
+        written: Written,
+    },
+    Move {
+        source: PathBuf,
+        target: PathBuf,
+        replaced: bool,
+        written: Written,
+    },
+    Undone {
+        at: SystemTime,
+    },
+}
+
+// Append-only log of everything a run did to the file system, one JSON object
+// per line. Resumed runs keep appending to the log they started.
+pub struct RunJournal {
+    id: String,
+    file: Box<dyn WriteHandle>,
+}
+
+impl RunJournal {
+    pub fn open(
+        fs: &dyn FileSystem,
+        id: &str,
+        input: &Path,
+        output: &Path,
+        mode: Mode,
+    ) -> io::Result<Self> {
+        ensure_runs_dir(fs)?;
+        let path = journal_path(id);
+        let is_new = !fs.exists(&path);
+
+        let mut journal = Self {
+            id: id.to_string(),
+            file: if is_new {
+                fs.create(&path)?
+            } else {
+                fs.open_append(&path)?
+            },
+        };
+        if is_new {
+            journal.record(&Operation::Start {
+                input: input.to_path_buf(),
+                output: output.to_path_buf(),
+                mode,
+                at: SystemTime::now(),
+            })?;
+            journal.sync()?;
+        }
+        Ok(journal)
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn record(&mut self, operation: &Operation) -> io::Result<()> {
+        let mut line = serde_json::to_vec(operation)?;
+        line.push(b'\n');
+        self.file.write_all(&line)
+    }
+
+    pub fn sync(&self) -> io::Result<()> {
+        self.file.sync_data()
+    }
+}
+
+pub fn journal_path(id: &str) -> PathBuf {
+    get_runs_dir().join(format!("{}.log", id))
+}
+
+// Accepts a run id or a path to a log file
+pub fn resolve_run(fs: &dyn FileSystem, run: &str) -> PathBuf {
+    let path = PathBuf::from(run);
+    if fs.exists(&path) {
+        path
+    } else {
+        journal_path(run)
+    }
+}
+
+pub fn read_run(fs: &dyn FileSystem, path: &Path) -> io::Result<Vec<Operation>> {
+    let content = fs.read_to_string(path)?;
+    let mut lines = content.lines().peekable();
+    let mut operations = Vec::new();
+
+    while let Some(line) = lines.next() {
+        match serde_json::from_str(line) {
+            Ok(operation) => operations.push(operation),
+            // A crash mid-write can only cut off the last line
+            Err(_) if lines.peek().is_none() => {}
+            Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+        }
+    }
+    Ok(operations)
+}
+
+pub fn mark_undone(fs: &dyn FileSystem, path: &Path) -> io::Result<()> {
+    let mut line = serde_json::to_vec(&Operation::Undone {
+        at: SystemTime::now(),
+    })?;
+    line.push(b'\n');
+
+    let mut file = fs.open_append(path)?;
+    file.write_all(&line)?;
+    file.sync_data()
+}
+
+pub fn list_runs(fs: &dyn FileSystem) -> io::Result<Vec<PathBuf>> {
+    let mut runs: Vec<_> = fs
+        .read_dir(&get_runs_dir())?
+        .into_iter()
+        .filter(|path| path.extension().and_then(|ext| ext.to_str()) == Some("log"))
+        .collect();
+    runs.sort();
+    Ok(runs)
+}
@@ -0,0 +1,123 @@
+use super::{Operation, Written};
+use crate::organizer::hash_file;
+use crate::vfs::FileSystem;
+use std::cmp::Reverse;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Default)]
+pub struct UndoReport {
+    pub reversed: usize,
+    // Everything left as it was, with the reason
+    pub skipped: Vec<(PathBuf, String)>,
+}
+
+impl UndoReport {
+    fn skip(&mut self, path: &Path, reason: impl Into<String>) {
+        self.skipped.push((path.to_path_buf(), reason.into()));
+    }
+}
+
+// Reverses a run's operations newest first, leaving anything alone that no
+// longer looks the way the run left it. Running it again after some were left
+// in place only picks up what is still there.
+pub fn undo_operations(fs: &dyn FileSystem, operations: &[Operation]) -> UndoReport {
+    let mut report = UndoReport::default();
+
+    // Workers log folders and the files going into them in any order, so
+    // folders are only removed once every file is gone, deepest first
+    let mut folders = Vec::new();
+    for operation in operations.iter().rev() {
+        match operation {
+            Operation::Copy {
+                target,
+                replaced,
+                written,
+                ..
+            } => {
+                if *replaced {
+                    report.skip(target, "replaced a file that was already there");
+                } else if !fs.exists(target) {
+                    // Removed by an earlier undo, or by hand
+                } else if let Err(reason) = check_unchanged(fs, target, written) {
+                    report.skip(target, reason);
+                } else {
+                    match fs.remove_file(target) {
+                        Ok(()) => report.reversed += 1,
+                        Err(e) => report.skip(target, e.to_string()),
+                    }
+                }
+            }
+            Operation::Move {
+                source,
+                target,
+                replaced,
+                written,
+            } => {
+                if *replaced {
+                    report.skip(target, "replaced a file that was already there");
+                } else if !fs.exists(target) && fs.exists(source) {
+                    // Moved back by an earlier undo, or logged for a rename
+                    // that failed and left the file to be copied instead
+                } else if fs.exists(source) {
+                    report.skip(target, "the original location is taken again");
+                } else if let Err(reason) = check_unchanged(fs, target, written) {
+                    report.skip(target, reason);
+                } else {
+                    match move_back(fs, target, source) {
+                        Ok(()) => report.reversed += 1,
+                        Err(e) => report.skip(target, format!("could not move back: {}", e)),
+                    }
+                }
+            }
+            Operation::Mkdir { path } => folders.push(path),
+            Operation::Start { .. } | Operation::Undone { .. } => {}
+        }
+    }
+
+    folders.sort_by_key(|path| Reverse(path.components().count()));
+    for path in folders {
+        match fs.remove_dir(path) {
+            Ok(()) => report.reversed += 1,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(_) => report.skip(path, "folder is not empty"),
+        }
+    }
+
+    report
+}
+
+fn check_unchanged(fs: &dyn FileSystem, path: &Path, written: &Written) -> Result<(), String> {
+    let meta = fs.metadata(path).map_err(|_| "file is gone".to_string())?;
+    if meta.len() != written.size || meta.modified().ok() != Some(written.modified) {
+        return Err("file was changed after the run".to_string());
+    }
+
+    if let Some(expected) = &written.hash
+        && hash_file(fs, path).ok().as_ref() != Some(expected)
+    {
+        return Err("file content was changed after the run".to_string());
+    }
+    Ok(())
+}
+
+fn move_back(fs: &dyn FileSystem, from: &Path, to: &Path) -> io::Result<()> {
+    if let Some(parent) = to.parent() {
+        fs.create_dir_all(parent)?;
+    }
+    if fs.rename(from, to).is_ok() {
+        return Ok(());
+    }
+
+    // Across file systems: copy, keep the permissions and modification time,
+    // then remove
+    let mut source = fs.open(from)?;
+    let meta = source.metadata()?;
+    let mut target = fs.create(to)?;
+    io::copy(&mut source, &mut target)?;
+    if let (Some(raw), Some(file)) = (meta.raw(), target.as_file()) {
+        file.set_permissions(raw.permissions())?;
+    }
+    target.set_modified(meta.modified()?)?;
+    fs.remove_file(from)
+}
@@ -0,0 +1,193 @@
+use super::journal;
+use crate::models::SaveState;
+use serde::Serialize;
+use serde_json::Value;
+use std::path::PathBuf;
+
+// Version 1 is the original bare `SaveState` dump without a header, version 2
+// the same state in a JSON envelope, version 3 the append-only journal and
+// version 4 allows more than one in-flight file
+pub const CURRENT_VERSION: u32 = 4;
+// Saves exported as a single JSON document keep using the version 2 envelope
+pub const JSON_VERSION: u32 = 2;
+const FORMAT_NAME: &str = "file-organizer-save";
+
+// Each entry upgrades the state from the version at its index + 1 to the next
+const MIGRATIONS: [fn(Value) -> Result<Value, String>; (CURRENT_VERSION - 1) as usize] =
+    [migrate_v1_to_v2, migrate_v2_to_v3, migrate_v3_to_v4];
+
+#[derive(Debug)]
+pub enum SaveFormatError {
+    Malformed(serde_json::Error),
+    NotASaveFile,
+    UnsupportedVersion(u32),
+    MigrationFailed { from: u32, reason: String },
+}
+
+impl std::fmt::Display for SaveFormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Malformed(e) => write!(f, "Save file is damaged: {}", e),
+            Self::NotASaveFile => write!(f, "Not a file-organizer save file"),
+            Self::UnsupportedVersion(version) => write!(
+                f,
+                "Save file uses format version {} but this build only reads up to version {}, please upgrade file-organizer",
+                version, CURRENT_VERSION
+            ),
+            Self::MigrationFailed { from, reason } => write!(
+                f,
+                "Could not upgrade save file from version {}: {}",
+                from, reason
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SaveFormatError {}
+
+impl From<serde_json::Error> for SaveFormatError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Malformed(e)
+    }
+}
+
+pub struct DecodedSave {
+    pub state: SaveState,
+    // The version the file was written in, before any migration
+    pub version: u32,
+    // Whether new entries can be appended to the file as it is on disk
+    pub appendable: bool,
+}
+
+#[derive(Serialize)]
+struct Envelope<'a> {
+    format: &'a str,
+    version: u32,
+    state: &'a SaveState,
+}
+
+pub fn encode_json(state: &SaveState) -> serde_json::Result<String> {
+    serde_json::to_string_pretty(&Envelope {
+        format: FORMAT_NAME,
+        version: JSON_VERSION,
+        state,
+    })
+}
+
+pub fn decode(content: &str) -> Result<DecodedSave, SaveFormatError> {
+    if journal::is_journal(content) {
+        decode_journal(content)
+    } else {
+        decode_json(content)
+    }
+}
+
+fn decode_journal(content: &str) -> Result<DecodedSave, SaveFormatError> {
+    let journal = journal::read(content)?;
+    if journal.version > CURRENT_VERSION {
+        return Err(SaveFormatError::UnsupportedVersion(journal.version));
+    }
+
+    let mut state: SaveState = serde_json::from_value(migrate(journal.state, journal.version)?)?;
+    state.processed_files = journal.entries;
+
+    Ok(DecodedSave {
+        state,
+        version: journal.version,
+        appendable: journal.clean,
+    })
+}
+
+fn decode_json(content: &str) -> Result<DecodedSave, SaveFormatError> {
+    let mut value: Value = serde_json::from_str(content)?;
+
+    let version = match value.get("version") {
+        None => 1,
+        Some(version) => version
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .ok_or(SaveFormatError::NotASaveFile)?,
+    };
+    if version > CURRENT_VERSION {
+        return Err(SaveFormatError::UnsupportedVersion(version));
+    }
+
+    let state = if version == 1 {
+        value
+    } else {
+        if value.get("format").and_then(Value::as_str) != Some(FORMAT_NAME) {
+            return Err(SaveFormatError::NotASaveFile);
+        }
+        value["state"].take()
+    };
+
+    Ok(DecodedSave {
+        state: serde_json::from_value(migrate(state, version)?)?,
+        version,
+        appendable: false,
+    })
+}
+
+fn migrate(mut state: Value, version: u32) -> Result<Value, SaveFormatError> {
+    for from in version..CURRENT_VERSION {
+        state = MIGRATIONS[(from - 1) as usize](state)
+            .map_err(|reason| SaveFormatError::MigrationFailed { from, reason })?;
+    }
+    Ok(state)
+}
+
+// Saves written before run settings were recorded stored the date folder of the
+// first copied file as the output root, so strip the "<type>/<date>" tail
+fn migrate_v1_to_v2(mut state: Value) -> Result<Value, String> {
+    let object = state.as_object_mut().ok_or("state is not an object")?;
+    if object.contains_key("settings") {
+        return Ok(state);
+    }
+
+    let output = object
+        .get("output_path")
+        .and_then(Value::as_str)
+        .map(PathBuf::from)
+        .ok_or("output_path is missing")?;
+
+    let is_date = |name: &str| chrono::NaiveDate::parse_from_str(name, "%Y-%m-%d").is_ok();
+    let is_type =
+        |name: &str| ["Video", "Music", "Document", "Picture", "Program", "Other"].contains(&name);
+
+    let tail: Vec<_> = output
+        .iter()
+        .rev()
+        .take(2)
+        .map(|c| c.to_string_lossy().to_string())
+        .collect();
+    if tail.len() == 2
+        && is_date(&tail[0])
+        && is_type(&tail[1])
+        && let Some(root) = output.parent().and_then(|p| p.parent())
+    {
+        object.insert(
+            "output_path".to_string(),
+            Value::String(root.to_string_lossy().to_string()),
+        );
+    }
+
+    Ok(state)
+}
+
+// Version 3 only changed the container, the state itself is unchanged
+fn migrate_v2_to_v3(state: Value) -> Result<Value, String> {
+    Ok(state)
+}
+
+// The single in-flight file became a list once files were copied in parallel.
+// JSON exports keep the version 2 envelope, so the list may already be there.
+fn migrate_v3_to_v4(mut state: Value) -> Result<Value, String> {
+    let object = state.as_object_mut().ok_or("state is not an object")?;
+    let in_flight = match object.remove("in_flight") {
+        None | Some(Value::Null) => Vec::new(),
+        Some(Value::Array(files)) => files,
+        Some(file) => vec![file],
+    };
+    object.insert("in_flight".to_string(), Value::Array(in_flight));
+    Ok(state)
+}
@@ -0,0 +1,139 @@
+use super::format::SaveFormatError;
+use crate::models::{ProcessedFile, SaveState};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::{Duration, UNIX_EPOCH};
+
+// One JSON value per line: a header, then processed entries and state records
+// in the order they were written. Entries only ever get appended, and the
+// last state record holds the current paths, settings and in-flight file.
+pub const JOURNAL_FORMAT: &str = "file-organizer-journal";
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    format: String,
+    version: u32,
+}
+
+// Processed entries are written as bare arrays to keep million-file runs small:
+// [path, size, mtime secs, mtime nanos, target, hash]
+#[derive(Serialize, Deserialize)]
+struct Entry(PathBuf, u64, u64, u32, Option<PathBuf>, Option<String>);
+
+#[derive(Serialize)]
+struct StateRecord<'a> {
+    state: &'a SaveState,
+}
+
+pub struct ReadJournal {
+    pub version: u32,
+    pub state: Value,
+    pub entries: Vec<ProcessedFile>,
+    // False when the last line was cut off, so appending to the file as it is
+    // would glue new data onto a broken line
+    pub clean: bool,
+}
+
+pub fn is_journal(content: &str) -> bool {
+    let first_line = content.lines().next().unwrap_or_default();
+    serde_json::from_str::<Header>(first_line).is_ok_and(|h| h.format == JOURNAL_FORMAT)
+}
+
+// Writes a complete journal for `state`
+pub fn write_full(writer: &mut impl Write, state: &mut SaveState, version: u32) -> io::Result<()> {
+    let header = Header {
+        format: JOURNAL_FORMAT.to_string(),
+        version,
+    };
+    serde_json::to_writer(&mut *writer, &header)?;
+    writer.write_all(b"\n")?;
+
+    append(writer, state, 0)
+}
+
+// Writes the entries from `from` onwards followed by a state record
+pub fn append(writer: &mut impl Write, state: &mut SaveState, from: usize) -> io::Result<()> {
+    for file in &state.processed_files[from..] {
+        serde_json::to_writer(&mut *writer, &to_entry(file)?)?;
+        writer.write_all(b"\n")?;
+    }
+
+    // The entries are already on their own lines, so leave them out of the record
+    let processed = std::mem::take(&mut state.processed_files);
+    let result = serde_json::to_writer(&mut *writer, &StateRecord { state });
+    state.processed_files = processed;
+    result?;
+    writer.write_all(b"\n")
+}
+
+pub fn read(content: &str) -> Result<ReadJournal, SaveFormatError> {
+    let mut lines = content.split_inclusive('\n').peekable();
+
+    let header: Header = serde_json::from_str(lines.next().unwrap_or_default())?;
+    if header.format != JOURNAL_FORMAT {
+        return Err(SaveFormatError::NotASaveFile);
+    }
+
+    let mut journal = ReadJournal {
+        version: header.version,
+        state: Value::Null,
+        entries: Vec::new(),
+        clean: true,
+    };
+
+    while let Some(line) = lines.next() {
+        let is_last = lines.peek().is_none();
+        let parsed = if line.starts_with('[') {
+            serde_json::from_str::<Entry>(line).map(|entry| journal.entries.push(from_entry(entry)))
+        } else {
+            serde_json::from_str::<Value>(line)
+                .map(|mut record| journal.state = record["state"].take())
+        };
+
+        match parsed {
+            Ok(()) if line.ends_with('\n') => {}
+            // A crash in the middle of an append only ever damages the last line
+            Ok(()) | Err(_) if is_last => journal.clean = false,
+            Ok(()) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    if journal.state.is_null() {
+        return Err(SaveFormatError::NotASaveFile);
+    }
+    Ok(journal)
+}
+
+fn to_entry(file: &ProcessedFile) -> io::Result<Entry> {
+    let modified = file
+        .modified
+        .duration_since(UNIX_EPOCH)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "modification time before 1970"))?;
+
+    Ok(Entry(
+        file.path.clone(),
+        file.size,
+        modified.as_secs(),
+        modified.subsec_nanos(),
+        file.target.clone(),
+        file.hash.clone(),
+    ))
+}
+
+fn from_entry(Entry(path, size, secs, nanos, target, hash): Entry) -> ProcessedFile {
+    ProcessedFile {
+        name: path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string(),
+        path,
+        size,
+        modified: UNIX_EPOCH + Duration::new(secs, nanos),
+        target,
+        hash,
+    }
+}
@@ -1,25 +1,25 @@
-use colored::*;
-use std::path::PathBuf;
+pub mod format;
+pub mod journal;
+
+use std::path::{Path, PathBuf};
 use crate::models::SaveState;
 use crate::error::OrganizeError;
+use crate::vfs::RealFs;
 
-pub fn save_progress(save_state: SaveState) -> Result<(), OrganizeError> {
-    let save_path = save_state
-        .save()
-        .map_err(|e| OrganizeError::FileCopyFailed(format!("Failed to save progress: {}", e)))?;
-
-    println!(
-        "\n{} {}",
-        "📝 Progress saved at:".bright_yellow(),
-        save_path.display()
-    );
-    Ok(())
+pub fn save_progress(mut save_state: SaveState, save_path: &Path) -> Result<(), OrganizeError> {
+    save_state
+        .save_to(&RealFs, save_path)
+        .map_err(|error| OrganizeError::SaveFile {
+            path: save_path.to_path_buf(),
+            error,
+        })
 }
 
-pub fn handle_save_cleanup(resume_path: Option<PathBuf>) {
-    if let Some(path) = resume_path {
-        if let Err(e) = std::fs::remove_file(path) {
-            eprintln!("{} {}", "Failed to clean up save file:".yellow(), e);
+pub fn handle_save_cleanup(save_path: Option<PathBuf>) -> Result<(), OrganizeError> {
+    match save_path {
+        Some(path) if path.exists() => {
+            std::fs::remove_file(&path).map_err(|error| OrganizeError::SaveFile { path, error })
         }
+        _ => Ok(()),
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,26 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Set when stdout carries machine-readable events, so what is meant for people
+// goes to stderr and a consumer can parse stdout as it is
+static HUMAN_ON_STDERR: AtomicBool = AtomicBool::new(false);
+
+pub fn send_human_output_to_stderr() {
+    HUMAN_ON_STDERR.store(true, Ordering::Relaxed);
+}
+
+pub fn human_output_on_stderr() -> bool {
+    HUMAN_ON_STDERR.load(Ordering::Relaxed)
+}
+
+// Like `println!`, for the header, messages and summaries meant for whoever
+// runs the program
+#[macro_export]
+macro_rules! say {
+    ($($arg:tt)*) => {
+        if $crate::ui::human_output_on_stderr() {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
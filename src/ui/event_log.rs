@@ -0,0 +1,145 @@
+use super::progress::format_size;
+use crate::organizer::{Event, Observer};
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+// One line per file and phase, for logs and terminals without the TUI
+pub struct TextLog<W: Write> {
+    out: W,
+}
+
+impl<W: Write> TextLog<W> {
+    pub fn new(out: W) -> Self {
+        Self { out }
+    }
+}
+
+impl<W: Write> Observer for TextLog<W> {
+    fn on_event(&mut self, event: &Event) {
+        let line = match event {
+            Event::Scanned { files, bytes } => {
+                format!("scanned   {} files, {}", files, format_size(*bytes))
+            }
+            Event::Conflict {
+                source,
+                target,
+                resolution,
+            } => format!(
+                "conflict  {} -> {} ({:?})",
+                source.display(),
+                target.display(),
+                resolution
+            ),
+            Event::Skipped { source, .. } => format!("skipped   {}", source.display()),
+            Event::Planned {
+                files,
+                bytes,
+                skipped,
+                unchanged,
+            } => format!(
+                "planned   {} files, {}, {} skipped, {} unchanged",
+                files,
+                format_size(*bytes),
+                skipped,
+                unchanged
+            ),
+            Event::FileRetry {
+                index,
+                attempt,
+                delay,
+                error,
+            } => format!(
+                "retry     #{} attempt {} in {:.1}s: {}",
+                index,
+                attempt,
+                delay.as_secs_f64(),
+                error
+            ),
+            Event::FileFailed { index, failure } => format!(
+                "failed    #{} {} while {}: {}",
+                index,
+                failure.path.display(),
+                failure.operation,
+                failure.message
+            ),
+            Event::FileDeferred { index, source } => {
+                format!("deferred  #{} {}, still changing", index, source.display())
+            }
+            Event::FileFinished {
+                index,
+                source,
+                target,
+                size,
+                replaced,
+            } => format!(
+                "done      #{} {} -> {} ({}{})",
+                index,
+                source.display(),
+                target.display(),
+                format_size(*size),
+                if *replaced { ", replaced" } else { "" }
+            ),
+            Event::Finished {
+                copied,
+                failed,
+                warnings,
+                retries,
+                interrupted,
+            } => format!(
+                "{} {} copied, {} failed, {} warnings, {} retries",
+                if *interrupted {
+                    "stopped  "
+                } else {
+                    "finished "
+                },
+                copied,
+                failed,
+                warnings,
+                retries
+            ),
+            Event::Scanning { .. }
+            | Event::FileQueued { .. }
+            | Event::FileStarted { .. }
+            | Event::FileProgress { .. }
+            | Event::FileVerified { .. } => {
+                return;
+            }
+        };
+        let _ = writeln!(self.out, "{}", line);
+    }
+}
+
+// Every event as a line of JSON. Byte progress is sent at most once a second.
+pub struct JsonLog<W: Write> {
+    out: W,
+    last_progress: Option<Instant>,
+}
+
+impl<W: Write> JsonLog<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            last_progress: None,
+        }
+    }
+}
+
+impl<W: Write> Observer for JsonLog<W> {
+    fn on_event(&mut self, event: &Event) {
+        if let Event::FileProgress { copied, size, .. } = event
+            && copied < size
+        {
+            if self
+                .last_progress
+                .is_some_and(|at| at.elapsed() < Duration::from_secs(1))
+            {
+                return;
+            }
+            self.last_progress = Some(Instant::now());
+        }
+
+        if let Ok(line) = serde_json::to_string(event) {
+            let _ = writeln!(self.out, "{}", line);
+        }
+    }
+}
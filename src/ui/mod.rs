@@ -1,7 +1,13 @@
+mod console;
 mod dialogs;
+mod event_log;
 mod output;
 pub(crate) mod progress;
+mod resume;
 
+pub use console::{human_output_on_stderr, send_human_output_to_stderr};
 pub use dialogs::get_output_location;
-pub use output::{cleanup, get_output_choice};
-pub use progress::{ProgressUI, ProgressUpdate};
+pub use event_log::{JsonLog, TextLog};
+pub use output::{cleanup, print_undo_report, get_output_choice};
+pub use progress::{ProgressObserver, ProgressUI, ProgressUpdate};
+pub use resume::{ResumeChoices, ask_resume_choices, print_resume_diff};
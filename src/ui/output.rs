@@ -1,10 +1,14 @@
-use colored::*;
-use dialoguer::{Select, theme::ColorfulTheme};
-use std::path::PathBuf;
+use crate::runs::undo::{UndoReport, undo_operations};
+use crate::runs::{Operation, journal_path, mark_undone, read_run};
+use crate::say;
 use crate::ui::get_output_location;
+use crate::vfs::RealFs;
+use colored::*;
+use dialoguer::{Confirm, Select, theme::ColorfulTheme};
+use std::path::{Path, PathBuf};
 
-pub fn get_output_choice(input_path: &PathBuf) -> PathBuf {
-    println!("\n{}", "📂 Select output location:".bright_cyan());
+pub fn get_output_choice(input_path: &Path) -> PathBuf {
+    say!("\n{}", "📂 Select output location:".bright_cyan());
 
     let options = vec!["Create folder next to input", "Choose custom location"];
     let selection = Select::with_theme(&ColorfulTheme::default())
@@ -32,13 +36,81 @@ pub fn get_output_choice(input_path: &PathBuf) -> PathBuf {
     }
 }
 
-pub fn cleanup(output_dir: &PathBuf) {
-    println!("{}", "\n🗑️  Cleaning up temporary files...".yellow());
-    if let Err(e) = std::fs::remove_dir_all(output_dir) {
-        eprintln!(
+// Removes what the run created, going by its log. Files and folders that were
+// there before the run, or that changed since, are never touched.
+pub fn cleanup(run_id: &str) {
+    let path = journal_path(run_id);
+    let Ok(operations) = read_run(&RealFs, &path) else {
+        return;
+    };
+
+    let created = operations
+        .iter()
+        .filter(|op| {
+            matches!(
+                op,
+                Operation::Copy {
+                    replaced: false,
+                    ..
+                } | Operation::Move {
+                    replaced: false,
+                    ..
+                } | Operation::Mkdir { .. }
+            )
+        })
+        .count();
+    if created == 0
+        || operations
+            .iter()
+            .any(|op| matches!(op, Operation::Undone { .. }))
+    {
+        return;
+    }
+
+    let confirmed = Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!(
+            "Remove the {} files and folders this run created? Moved files go back where they were",
+            created
+        ))
+        .default(false)
+        .interact()
+        .unwrap_or(false);
+    if !confirmed {
+        say!(
             "{} {}",
-            "Failed to clean up directory:".red(),
-            e.to_string().bright_red()
+            "Output kept. To remove it later, run".yellow(),
+            format!("file-organizer undo {}", run_id).bright_cyan()
         );
+        return;
+    }
+
+    say!("{}", "\n🗑️  Cleaning up what this run created...".yellow());
+    let report = undo_operations(&RealFs, &operations);
+    // What was left in place can still be retried with `undo`
+    if report.skipped.is_empty()
+        && let Err(e) = mark_undone(&RealFs, &path)
+    {
+        eprintln!("{} {}", "Failed to update the run log:".yellow(), e);
+    }
+    print_undo_report(&report);
+}
+
+pub fn print_undo_report(report: &UndoReport) {
+    say!(
+        "\n{} {}",
+        "↩️  Operations reversed:".bright_green(),
+        report.reversed
+    );
+    if report.skipped.is_empty() {
+        return;
     }
-}
\ No newline at end of file
+
+    say!(
+        "\n{} {}",
+        "⚠️  Left in place:".yellow(),
+        report.skipped.len().to_string().yellow()
+    );
+    for (path, reason) in &report.skipped {
+        say!("  {} {}", path.display(), reason.dimmed());
+    }
+}
@@ -1,6 +1,7 @@
 use crate::models::FileType;
+use crate::organizer::{Event as RunEvent, Observer};
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
+    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers},
     execute,
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
@@ -13,12 +14,17 @@ use ratatui::{
     widgets::{Block, Borders, Gauge, List, ListItem, Paragraph},
 };
 use std::{
+    collections::{BTreeMap, VecDeque},
     io,
-    sync::mpsc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, TryRecvError},
+    },
     time::{Duration, Instant},
 };
 
 pub struct ProgressState {
+    // Files planned so far, final once the scan is done
     pub total_files: u64,
     pub current_file_index: u64,
     pub current_file: String,
@@ -30,14 +36,22 @@ pub struct ProgressState {
     pub recent_files: Vec<String>,
     pub total_bytes: u64,
     pub estimated_time: Option<f64>,
-    pub file_queue: Vec<(String, u64)>, // (filename, size) pairs for upcoming files
+    pub file_queue: VecDeque<(u64, String, u64)>, // (index, filename, size) for upcoming files
+    pub scanned_files: u64,
+    pub scan_done: bool,
     pub last_file: Option<String>,
+    // When the speed was last worked out, and the bytes done at that point
+    pub speed_sample: (Instant, u64),
+    pub retries: u64,
+    pub last_retry: Option<String>,
+    // Set while the current file waits to be tried again
+    pub retrying: bool,
 }
 
 impl ProgressState {
-    pub fn new(total_files: u64) -> Self {
+    pub fn new() -> Self {
         ProgressState {
-            total_files,
+            total_files: 0,
             current_file_index: 0,
             current_file: String::new(),
             current_file_size: 0,
@@ -48,13 +62,22 @@ impl ProgressState {
             recent_files: Vec::new(),
             total_bytes: 0,
             estimated_time: None,
-            file_queue: Vec::new(),
+            file_queue: VecDeque::new(),
+            scanned_files: 0,
+            scan_done: false,
             last_file: None,
+            speed_sample: (Instant::now(), 0),
+            retries: 0,
+            last_retry: None,
+            retrying: false,
         }
     }
 
-    pub fn set_file_queue(&mut self, files: Vec<(String, u64)>) {
-        self.file_queue = files;
+    // Files are queued only a bounded way ahead of the copy, so the queue
+    // stays short
+    pub fn queue_file(&mut self, index: u64, name: String, size: u64) {
+        self.total_files = self.total_files.max(index);
+        self.file_queue.push_back((index, name, size));
     }
 
     pub fn update_file_progress(
@@ -63,17 +86,30 @@ impl ProgressState {
         size: u64,
         progress: u64,
         index: u64,
-        bps: f64,
         total_bytes: u64,
-        estimated_time: Option<f64>,
     ) {
+        let (sampled_at, sampled_bytes) = self.speed_sample;
+        let elapsed = sampled_at.elapsed().as_secs_f64();
+        if elapsed >= 0.5 {
+            self.bytes_per_second = total_bytes.saturating_sub(sampled_bytes) as f64 / elapsed;
+            self.speed_sample = (Instant::now(), total_bytes);
+        }
+
         self.current_file = file_name.clone();
         self.current_file_size = size;
         self.current_file_progress = progress;
         self.current_file_index = index;
-        self.bytes_per_second = bps;
+        while self
+            .file_queue
+            .front()
+            .is_some_and(|(queued, _, _)| *queued <= index)
+        {
+            self.file_queue.pop_front();
+        }
         self.total_bytes = total_bytes;
-        self.estimated_time = estimated_time;
+        self.estimated_time = (self.bytes_per_second > 0.0)
+            .then(|| size.saturating_sub(progress) as f64 / self.bytes_per_second);
+        self.retrying = false;
         if self.last_file.is_some() && self.last_file != Some(file_name.clone()) {
             self.recent_files.push(self.last_file.take().unwrap());
         }
@@ -87,7 +123,7 @@ pub struct ProgressUI {
 }
 
 impl ProgressUI {
-    pub fn new(total_files: u64) -> io::Result<Self> {
+    pub fn new() -> io::Result<Self> {
         enable_raw_mode()?;
         let mut stdout = io::stdout();
         execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
@@ -96,12 +132,23 @@ impl ProgressUI {
 
         Ok(Self {
             terminal,
-            state: ProgressState::new(total_files),
+            state: ProgressState::new(),
         })
     }
 
-    pub fn run(&mut self, rx: mpsc::Receiver<ProgressUpdate>) -> io::Result<()> {
+    // Runs until the processing thread hangs up. 'q' stops the run and leaves
+    // the choice of saving to the user, Ctrl+C stops it and saves right away.
+    pub fn run(
+        &mut self,
+        rx: mpsc::Receiver<ProgressUpdate>,
+        stop_signal: &AtomicBool,
+        auto_save: &AtomicBool,
+    ) -> io::Result<()> {
         loop {
+            if stop_signal.load(Ordering::SeqCst) {
+                self.state.is_stopping = true;
+            }
+
             let ui_state = &self.state;
             self.terminal.draw(|f| {
                 let chunks = Layout::default()
@@ -122,34 +169,62 @@ impl ProgressUI {
                 Self::render_recent_files(ui_state, f, chunks[2]);
             })?;
 
-            if event::poll(Duration::from_millis(100))? {
-                if let Event::Key(key) = event::read()? {
-                    if key.code == KeyCode::Char('q') {
-                        return Ok(());
+            if event::poll(Duration::from_millis(100))?
+                && let Event::Key(key) = event::read()?
+            {
+                match key.code {
+                    KeyCode::Char('q') => stop_signal.store(true, Ordering::SeqCst),
+                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        auto_save.store(true, Ordering::SeqCst);
+                        stop_signal.store(true, Ordering::SeqCst);
                     }
+                    _ => {}
                 }
             }
 
-            if let Ok(update) = rx.try_recv() {
+            loop {
+                let update = match rx.try_recv() {
+                    Ok(update) => update,
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => return Ok(()),
+                };
+
                 match update {
                     ProgressUpdate::File {
                         name,
                         size,
                         progress,
                         index,
-                        bytes_per_second,
                         total_bytes,
-                        estimated_time,
                     } => {
-                        self.state.update_file_progress(
+                        self.state
+                            .update_file_progress(name, size, progress, index, total_bytes);
+                    }
+                    ProgressUpdate::Retry {
+                        name,
+                        attempt,
+                        delay,
+                        error,
+                    } => {
+                        self.state.retries += 1;
+                        self.state.retrying = true;
+                        self.state.last_retry = Some(format!(
+                            "{} (attempt {}, waiting {:.1}s): {}",
                             name,
-                            size,
-                            progress,
-                            index,
-                            bytes_per_second,
-                            total_bytes,
-                            estimated_time,
-                        );
+                            attempt,
+                            delay.as_secs_f64(),
+                            error
+                        ));
+                    }
+                    ProgressUpdate::Scanning { files } => {
+                        self.state.scanned_files = files;
+                    }
+                    ProgressUpdate::Queued { index, name, size } => {
+                        self.state.queue_file(index, name, size);
+                    }
+                    ProgressUpdate::Planned { files } => {
+                        self.state.total_files = files;
+                        self.state.scan_done = true;
                     }
                     ProgressUpdate::Stop => {
                         self.state.is_stopping = true;
@@ -162,16 +237,16 @@ impl ProgressUI {
         }
     }
 
-    pub fn set_file_queue(&mut self, files: Vec<(String, u64)>) {
-        self.state.set_file_queue(files);
-    }
-
     fn render_total_progress(state: &ProgressState, f: &mut Frame, area: Rect) {
-        let ratio = state.current_file_index as f64 / state.total_files as f64;
+        let ratio = if state.total_files > 0 {
+            (state.current_file_index as f64 / state.total_files as f64).min(1.0)
+        } else {
+            0.0
+        };
         let percentage = (ratio * 100.0) as u64;
 
         // Calculate processed and remaining files
-        let files_remaining = state.total_files - state.current_file_index;
+        let files_remaining = state.total_files.saturating_sub(state.current_file_index);
         let processed_bytes = format_size(state.total_bytes);
 
         // Calculate average speed and estimated total time
@@ -182,16 +257,20 @@ impl ProgressUI {
             0.0
         };
 
-        // Format the progress details
-        let progress_text = format!(
-            "{}/{} files ({}%) | Processed: {} | Avg Speed: {}/s | Remaining: {} files",
+        // Format the progress details, totals only grow while the scan runs
+        let mut progress_text = format!(
+            "{}/{}{} files ({}%) | Processed: {} | Avg Speed: {}/s | Remaining: {} files",
             state.current_file_index,
             state.total_files,
+            if state.scan_done { "" } else { "+" },
             percentage,
             processed_bytes,
             format_size(avg_speed as u64),
             files_remaining
         );
+        if !state.scan_done {
+            progress_text.push_str(&format!(" | Scanning: {} found", state.scanned_files));
+        }
 
         let gauge = Gauge::default()
             .block(
@@ -224,6 +303,8 @@ impl ProgressUI {
 
         let title = if state.is_stopping {
             "Current File (Stopping...)"
+        } else if state.retrying {
+            "Current File (Retrying...)"
         } else {
             "Current File"
         };
@@ -304,11 +385,10 @@ impl ProgressUI {
             let files_that_fit = remaining_height.saturating_sub(current_items.len());
 
             // Show upcoming files from the queue
-            let start_idx = state.current_file_index as usize;
-            for (filename, size) in state
+            for (_, filename, size) in state
                 .file_queue
                 .iter()
-                .skip(start_idx + 1)
+                .filter(|(index, _, _)| *index > state.current_file_index)
                 .take(files_that_fit)
             {
                 current_items.push(ListItem::new(Line::from(vec![
@@ -432,6 +512,14 @@ impl ProgressUI {
                 Span::styled("Avg File Size: ", Style::default().fg(Color::Yellow)),
                 Span::raw(format_size(avg_file_size as u64)),
             ]),
+            Line::from(vec![
+                Span::styled("Retries: ", Style::default().fg(Color::Yellow)),
+                Span::raw(state.retries.to_string()),
+            ]),
+            Line::from(Span::styled(
+                state.last_retry.clone().unwrap_or_default(),
+                Style::default().fg(Color::Red),
+            )),
         ])
         .block(
             Block::default()
@@ -469,14 +557,162 @@ pub enum ProgressUpdate {
         size: u64,
         progress: u64,
         index: u64,
-        bytes_per_second: f64,
+        // Bytes done across the whole run, the current file included
         total_bytes: u64,
-        estimated_time: Option<f64>,
+    },
+    Scanning {
+        files: u64,
+    },
+    Queued {
+        index: u64,
+        name: String,
+        size: u64,
+    },
+    Planned {
+        files: u64,
+    },
+    Retry {
+        name: String,
+        attempt: u32,
+        delay: Duration,
+        error: String,
     },
     Stop,
     Complete,
 }
 
+// Turns the run's events into updates for the progress screen, sending byte
+// progress at most every 50ms. While several files copy at once the screen
+// follows the earliest of them in the plan.
+pub struct ProgressObserver {
+    tx: mpsc::Sender<ProgressUpdate>,
+    // Name, size and bytes copied of the files being copied, by index
+    active: BTreeMap<usize, (String, u64, u64)>,
+    done_bytes: u64,
+    last_sent: Instant,
+}
+
+impl ProgressObserver {
+    pub fn new(tx: mpsc::Sender<ProgressUpdate>) -> Self {
+        Self {
+            tx,
+            active: BTreeMap::new(),
+            done_bytes: 0,
+            last_sent: Instant::now(),
+        }
+    }
+
+    fn send_file(&mut self, index: usize, name: String, size: u64, progress: u64) {
+        self.last_sent = Instant::now();
+        let copying: u64 = self.active.values().map(|(_, _, copied)| copied).sum();
+        let _ = self.tx.send(ProgressUpdate::File {
+            name,
+            size,
+            progress,
+            index: index as u64,
+            total_bytes: self.done_bytes + copying,
+        });
+    }
+
+    fn send_earliest(&mut self) {
+        if let Some((&index, (name, size, progress))) = self.active.first_key_value() {
+            let (name, size, progress) = (name.clone(), *size, *progress);
+            self.send_file(index, name, size, progress);
+        }
+    }
+
+    // Shows the next file still copying, or the one that just ended when it
+    // was the last
+    fn ended(&mut self, index: usize, progress: u64) {
+        let Some((name, size, _)) = self.active.remove(&index) else {
+            return;
+        };
+        if self.active.is_empty() {
+            self.send_file(index, name, size, progress);
+        } else {
+            self.send_earliest();
+        }
+    }
+}
+
+impl Observer for ProgressObserver {
+    fn on_event(&mut self, event: &RunEvent) {
+        match *event {
+            RunEvent::Scanning { files, .. } | RunEvent::Scanned { files, .. } => {
+                let _ = self.tx.send(ProgressUpdate::Scanning {
+                    files: files as u64,
+                });
+            }
+            RunEvent::FileQueued {
+                index,
+                source,
+                size,
+                ..
+            } => {
+                let _ = self.tx.send(ProgressUpdate::Queued {
+                    index: index as u64,
+                    name: file_name(source),
+                    size,
+                });
+            }
+            RunEvent::Planned { files, .. } => {
+                let _ = self.tx.send(ProgressUpdate::Planned {
+                    files: files as u64,
+                });
+            }
+            RunEvent::FileStarted {
+                index,
+                source,
+                size,
+                ..
+            } => {
+                self.active.insert(index, (file_name(source), size, 0));
+                self.send_earliest();
+            }
+            RunEvent::FileProgress { index, copied, .. } => {
+                if let Some((_, _, progress)) = self.active.get_mut(&index) {
+                    *progress = copied;
+                }
+                if self.last_sent.elapsed() >= Duration::from_millis(50) {
+                    self.send_earliest();
+                }
+            }
+            RunEvent::FileRetry {
+                index,
+                attempt,
+                delay,
+                error,
+            } => {
+                let _ = self.tx.send(ProgressUpdate::Retry {
+                    name: self
+                        .active
+                        .get(&index)
+                        .map(|(name, _, _)| name.clone())
+                        .unwrap_or_default(),
+                    attempt,
+                    delay,
+                    error: error.to_string(),
+                });
+            }
+            RunEvent::FileFinished { index, size, .. } => {
+                self.done_bytes += size;
+                self.ended(index, size);
+            }
+            RunEvent::FileFailed { index, .. } | RunEvent::FileDeferred { index, .. } => {
+                self.ended(index, 0)
+            }
+            _ => {}
+        }
+    }
+}
+
+fn file_name(path: &std::path::Path) -> String {
+    path.file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string()
+}
+
 pub fn format_size(size: u64) -> String {
     const UNITS: [&str; 6] = ["B", "KB", "MB", "GB", "TB", "PB"];
     let mut size = size as f64;
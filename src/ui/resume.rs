@@ -0,0 +1,81 @@
+use crate::organizer::ResumeDiff;
+use crate::say;
+use colored::*;
+use dialoguer::{Select, theme::ColorfulTheme};
+use std::path::PathBuf;
+
+const MAX_LISTED: usize = 20;
+
+pub struct ResumeChoices {
+    pub recopy_changed: bool,
+    pub include_new: bool,
+}
+
+pub fn print_resume_diff(diff: &ResumeDiff) {
+    say!(
+        "\n{}",
+        "⚠️  Files changed since the save was made:".yellow()
+    );
+
+    if !diff.changed.is_empty() {
+        say!("{} {}", "Changed:".yellow(), diff.changed.len());
+        for file in diff.changed.iter().take(MAX_LISTED) {
+            say!(
+                "  {} {} {}",
+                "~".yellow(),
+                file.path.display(),
+                format!("({})", file.reason).dimmed()
+            );
+        }
+        print_overflow(diff.changed.len());
+    }
+
+    print_paths("Vanished:", "-", &diff.vanished);
+    print_paths("New:", "+", &diff.new);
+}
+
+fn print_paths(title: &str, marker: &str, paths: &[PathBuf]) {
+    if paths.is_empty() {
+        return;
+    }
+
+    say!("{} {}", title.yellow(), paths.len());
+    for path in paths.iter().take(MAX_LISTED) {
+        say!("  {} {}", marker.yellow(), path.display());
+    }
+    print_overflow(paths.len());
+}
+
+fn print_overflow(count: usize) {
+    if count > MAX_LISTED {
+        say!(
+            "  {}",
+            format!("...and {} more", count - MAX_LISTED).dimmed()
+        );
+    }
+}
+
+pub fn ask_resume_choices(diff: &ResumeDiff) -> ResumeChoices {
+    let recopy_changed = !diff.changed.is_empty()
+        && Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("What should happen to changed files?")
+            .items(&["Re-copy them", "Skip them"])
+            .default(0)
+            .interact()
+            .unwrap_or(0)
+            == 0;
+
+    let include_new = !diff.new.is_empty()
+        && Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("What should happen to new files?")
+            .items(&["Organize them too", "Skip them"])
+            .default(0)
+            .interact()
+            .unwrap_or(0)
+            == 0;
+
+    ResumeChoices {
+        recopy_changed,
+        include_new,
+    }
+}
@@ -1,28 +1,43 @@
-use std::fs;
-use std::path::PathBuf;
+use crate::vfs::FileSystem;
+use std::path::{Path, PathBuf};
 
+// Only works out the path, `ensure_save_dir` creates it
 pub fn get_save_dir() -> PathBuf {
-    let app_data = dirs::data_local_dir()
+    dirs::data_local_dir()
         .unwrap_or_else(|| PathBuf::from("."))
-        .join("file-organizer");
+        .join("file-organizer")
+        .join("saves")
+}
 
-    // Create the directory if it doesn't exist
-    if !app_data.exists() {
-        fs::create_dir_all(&app_data).expect("Failed to create application data directory");
-    }
+pub fn get_runs_dir() -> PathBuf {
+    get_save_dir().with_file_name("runs")
+}
 
-    app_data.join("saves")
+pub fn get_index_path() -> PathBuf {
+    get_save_dir().with_file_name("index.redb")
 }
 
-pub fn ensure_save_dir() -> std::io::Result<()> {
+pub fn ensure_runs_dir(fs: &dyn FileSystem) -> std::io::Result<()> {
+    let runs_dir = get_runs_dir();
+    if !fs.exists(&runs_dir) {
+        fs.create_dir_all(&runs_dir)?;
+    }
+    Ok(())
+}
+
+pub fn ensure_save_dir(fs: &dyn FileSystem) -> std::io::Result<()> {
     let save_dir = get_save_dir();
-    if !save_dir.exists() {
-        fs::create_dir_all(save_dir)?;
+    if !fs.exists(&save_dir) {
+        fs.create_dir_all(&save_dir)?;
     }
     Ok(())
 }
 
-pub fn generate_save_filename(input_path: &PathBuf) -> String {
+pub fn generate_save_filename(input_path: &Path) -> String {
+    format!("{}.forg", generate_run_id(input_path))
+}
+
+pub fn generate_run_id(input_path: &Path) -> String {
     use chrono::Local;
 
     let timestamp = Local::now().format("%Y%m%d_%H%M%S");
@@ -34,5 +49,5 @@ pub fn generate_save_filename(input_path: &PathBuf) -> String {
         .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
         .collect::<String>();
 
-    format!("{}_{}.forg", folder_name, timestamp)
+    format!("{}_{}", folder_name, timestamp)
 }
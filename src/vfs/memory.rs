@@ -0,0 +1,540 @@
+use super::{FileMeta, FileSystem, ReadHandle, WriteHandle};
+use std::collections::BTreeMap;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime};
+
+// 2024-01-01, so the dates files are organized under don't depend on the clock
+const DEFAULT_TIME: Duration = Duration::from_secs(1_704_067_200);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultOp {
+    Metadata,
+    Open,
+    Create,
+    Read,
+    Write,
+    Sync,
+    Rename,
+    Remove,
+    CreateDir,
+}
+
+// An error handed out in place of an operation
+#[derive(Debug, Clone)]
+pub struct Fault {
+    op: FaultOp,
+    kind: io::ErrorKind,
+    path: Option<PathBuf>,
+    after: u32,
+    times: Option<u32>,
+}
+
+impl Fault {
+    pub fn new(op: FaultOp, kind: io::ErrorKind) -> Self {
+        Self {
+            op,
+            kind,
+            path: None,
+            after: 0,
+            times: None,
+        }
+    }
+
+    // Only fail for this path or anything below it
+    pub fn on(mut self, path: impl Into<PathBuf>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    // Let the first `after` matching operations through, to fail partway into
+    // a file
+    pub fn after(mut self, after: u32) -> Self {
+        self.after = after;
+        self
+    }
+
+    // Only fail the first `times` matching operations, then let them through.
+    // Zero never fails.
+    pub fn times(mut self, times: u32) -> Self {
+        self.times = Some(times);
+        self
+    }
+}
+
+struct FileData {
+    bytes: Vec<u8>,
+    modified: SystemTime,
+}
+
+type Data = Arc<Mutex<FileData>>;
+
+enum Node {
+    Dir,
+    // Shared with open handles, so renaming a file doesn't pull it out from
+    // under them
+    File(Data),
+}
+
+#[derive(Default)]
+struct State {
+    nodes: BTreeMap<PathBuf, Node>,
+    faults: Vec<Fault>,
+    capacity: Option<u64>,
+}
+
+// A file system kept in memory, for playing runs through without touching the
+// disk. Cloning it gives another handle to the same files.
+//
+// Locks are always taken state first, then file data.
+#[derive(Clone, Default)]
+pub struct MemoryFs {
+    state: Arc<Mutex<State>>,
+}
+
+impl MemoryFs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Adds a file along with any folders above it
+    pub fn add_file(&self, path: impl AsRef<Path>, contents: impl Into<Vec<u8>>) {
+        let path = path.as_ref();
+        let mut state = self.lock();
+        if let Some(parent) = path.parent() {
+            state.make_dirs(parent);
+        }
+        state.nodes.insert(
+            path.to_path_buf(),
+            Node::File(Arc::new(Mutex::new(FileData {
+                bytes: contents.into(),
+                modified: SystemTime::UNIX_EPOCH + DEFAULT_TIME,
+            }))),
+        );
+    }
+
+    pub fn add_dir(&self, path: impl AsRef<Path>) {
+        self.lock().make_dirs(path.as_ref());
+    }
+
+    // Sets both the creation and modification time
+    pub fn set_modified(&self, path: impl AsRef<Path>, time: SystemTime) {
+        if let Some(Node::File(data)) = self.lock().nodes.get(path.as_ref()) {
+            lock(data).modified = time;
+        }
+    }
+
+    pub fn contents(&self, path: impl AsRef<Path>) -> Option<Vec<u8>> {
+        match self.lock().nodes.get(path.as_ref()) {
+            Some(Node::File(data)) => Some(lock(data).bytes.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn files(&self) -> Vec<PathBuf> {
+        self.lock()
+            .nodes
+            .iter()
+            .filter(|(_, node)| matches!(node, Node::File(_)))
+            .map(|(path, _)| path.clone())
+            .collect()
+    }
+
+    pub fn inject(&self, fault: Fault) {
+        self.lock().faults.push(fault);
+    }
+
+    pub fn clear_faults(&self) {
+        self.lock().faults.clear();
+    }
+
+    // Writes that would take the files past this many bytes fail as if the
+    // disk were full
+    pub fn set_capacity(&self, bytes: Option<u64>) {
+        self.lock().capacity = bytes;
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        lock(&self.state)
+    }
+
+    fn handle(&self, path: &Path, append: bool) -> io::Result<Box<dyn WriteHandle>> {
+        let mut state = self.lock();
+        state.fail(FaultOp::Open, path)?;
+        let data = state.file(path)?;
+        Ok(Box::new(Handle {
+            state: Arc::clone(&self.state),
+            path: path.to_path_buf(),
+            data,
+            pos: 0,
+            append,
+        }))
+    }
+}
+
+impl State {
+    fn fail(&mut self, op: FaultOp, path: &Path) -> io::Result<()> {
+        let Some(index) = self.faults.iter().position(|fault| {
+            fault.op == op
+                && fault.times != Some(0)
+                && fault.path.as_ref().is_none_or(|p| path.starts_with(p))
+        }) else {
+            return Ok(());
+        };
+
+        let fault = &mut self.faults[index];
+        if fault.after > 0 {
+            fault.after -= 1;
+            return Ok(());
+        }
+        let kind = fault.kind;
+        if let Some(times) = fault.times.as_mut() {
+            *times -= 1;
+            if *times == 0 {
+                self.faults.remove(index);
+            }
+        }
+        Err(io::Error::new(
+            kind,
+            format!("injected {:?} fault on '{}'", op, path.display()),
+        ))
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        path.parent().is_none()
+            || path.as_os_str().is_empty()
+            || matches!(self.nodes.get(path), Some(Node::Dir))
+    }
+
+    fn file(&self, path: &Path) -> io::Result<Data> {
+        match self.nodes.get(path) {
+            Some(Node::File(data)) => Ok(Arc::clone(data)),
+            Some(Node::Dir) => Err(io::ErrorKind::IsADirectory.into()),
+            None => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+
+    fn check_parent(&self, path: &Path) -> io::Result<()> {
+        match path.parent() {
+            Some(parent) if !self.is_dir(parent) => Err(io::ErrorKind::NotFound.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn make_dirs(&mut self, path: &Path) {
+        for dir in path.ancestors().filter(|dir| dir.parent().is_some()) {
+            self.nodes.entry(dir.to_path_buf()).or_insert(Node::Dir);
+        }
+    }
+
+    fn used(&self) -> u64 {
+        self.nodes
+            .values()
+            .map(|node| match node {
+                Node::File(data) => lock(data).bytes.len() as u64,
+                Node::Dir => 0,
+            })
+            .sum()
+    }
+}
+
+impl FileSystem for MemoryFs {
+    fn metadata(&self, path: &Path) -> io::Result<FileMeta> {
+        let mut state = self.lock();
+        state.fail(FaultOp::Metadata, path)?;
+        if state.is_dir(path) {
+            return Ok(FileMeta {
+                len: 0,
+                is_dir: true,
+                modified: Some(SystemTime::UNIX_EPOCH + DEFAULT_TIME),
+                created: Some(SystemTime::UNIX_EPOCH + DEFAULT_TIME),
+                device: None,
+                inode: None,
+                raw: None,
+            });
+        }
+        let data = state.file(path)?;
+        Ok(meta_of(&lock(&data)))
+    }
+
+    fn walk_files(
+        &self,
+        root: &Path,
+        _threads: usize,
+    ) -> Box<dyn Iterator<Item = (PathBuf, FileMeta)> + Send + '_> {
+        // Listed up front so the walk doesn't hold the lock
+        let mut state = self.lock();
+        let files: Vec<_> = state
+            .nodes
+            .range(root.to_path_buf()..)
+            .take_while(|(path, _)| path.starts_with(root))
+            .filter_map(|(path, node)| match node {
+                Node::File(data) => Some((path.clone(), meta_of(&lock(data)))),
+                Node::Dir => None,
+            })
+            .collect();
+        // Files whose metadata fails are left out, as on disk
+        let files: Vec<_> = files
+            .into_iter()
+            .filter(|(path, _)| state.fail(FaultOp::Metadata, path).is_ok())
+            .collect();
+        Box::new(files.into_iter())
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        let mut state = self.lock();
+        state.fail(FaultOp::Open, path)?;
+        if !state.is_dir(path) {
+            return Err(io::ErrorKind::NotFound.into());
+        }
+        Ok(state
+            .nodes
+            .keys()
+            .filter(|child| child.parent() == Some(path))
+            .cloned()
+            .collect())
+    }
+
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        let mut state = self.lock();
+        state.fail(FaultOp::CreateDir, path)?;
+        if state.nodes.contains_key(path) {
+            return Err(io::ErrorKind::AlreadyExists.into());
+        }
+        state.check_parent(path)?;
+        state.nodes.insert(path.to_path_buf(), Node::Dir);
+        Ok(())
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        let mut state = self.lock();
+        state.fail(FaultOp::CreateDir, path)?;
+        if path
+            .ancestors()
+            .any(|dir| matches!(state.nodes.get(dir), Some(Node::File(_))))
+        {
+            return Err(io::ErrorKind::NotADirectory.into());
+        }
+        state.make_dirs(path);
+        Ok(())
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn ReadHandle>> {
+        let mut state = self.lock();
+        state.fail(FaultOp::Open, path)?;
+        let data = state.file(path)?;
+        Ok(Box::new(Handle {
+            state: Arc::clone(&self.state),
+            path: path.to_path_buf(),
+            data,
+            pos: 0,
+            append: false,
+        }))
+    }
+
+    fn remove_dir(&self, path: &Path) -> io::Result<()> {
+        let mut state = self.lock();
+        state.fail(FaultOp::Remove, path)?;
+        match state.nodes.get(path) {
+            Some(Node::Dir) => {}
+            Some(Node::File(_)) => return Err(io::ErrorKind::NotADirectory.into()),
+            None => return Err(io::ErrorKind::NotFound.into()),
+        }
+        if state.nodes.keys().any(|child| child.parent() == Some(path)) {
+            return Err(io::ErrorKind::DirectoryNotEmpty.into());
+        }
+        state.nodes.remove(path);
+        Ok(())
+    }
+
+    fn create(&self, path: &Path) -> io::Result<Box<dyn WriteHandle>> {
+        {
+            let mut state = self.lock();
+            state.fail(FaultOp::Create, path)?;
+            state.check_parent(path)?;
+            match state.nodes.get(path) {
+                Some(Node::File(data)) => lock(data).bytes.clear(),
+                Some(Node::Dir) => return Err(io::ErrorKind::IsADirectory.into()),
+                None => {
+                    state.nodes.insert(
+                        path.to_path_buf(),
+                        Node::File(Arc::new(Mutex::new(FileData {
+                            bytes: Vec::new(),
+                            modified: SystemTime::now(),
+                        }))),
+                    );
+                }
+            }
+        }
+        self.handle(path, false)
+    }
+
+    fn open_write(&self, path: &Path) -> io::Result<Box<dyn WriteHandle>> {
+        self.handle(path, false)
+    }
+
+    fn open_append(&self, path: &Path) -> io::Result<Box<dyn WriteHandle>> {
+        self.handle(path, true)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let mut state = self.lock();
+        state.fail(FaultOp::Rename, from)?;
+        state.check_parent(to)?;
+        if matches!(state.nodes.get(to), Some(Node::Dir)) {
+            return Err(io::ErrorKind::IsADirectory.into());
+        }
+
+        let moved: Vec<PathBuf> = state
+            .nodes
+            .range(from.to_path_buf()..)
+            .map(|(path, _)| path)
+            .take_while(|path| path.starts_with(from))
+            .cloned()
+            .collect();
+        if moved.is_empty() {
+            return Err(io::ErrorKind::NotFound.into());
+        }
+        for path in moved {
+            let node = state.nodes.remove(&path).expect("listed above");
+            let rest = path.strip_prefix(from).expect("listed above");
+            // Joining an empty rest would leave a trailing separator
+            let target = if rest.as_os_str().is_empty() {
+                to.to_path_buf()
+            } else {
+                to.join(rest)
+            };
+            state.nodes.insert(target, node);
+        }
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        let mut state = self.lock();
+        state.fail(FaultOp::Remove, path)?;
+        state.file(path)?;
+        state.nodes.remove(path);
+        Ok(())
+    }
+}
+
+struct Handle {
+    state: Arc<Mutex<State>>,
+    path: PathBuf,
+    data: Data,
+    pos: u64,
+    append: bool,
+}
+
+impl Read for Handle {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        lock(&self.state).fail(FaultOp::Read, &self.path)?;
+        let data = lock(&self.data);
+        let start = (self.pos as usize).min(data.bytes.len());
+        let read = buf.len().min(data.bytes.len() - start);
+        buf[..read].copy_from_slice(&data.bytes[start..start + read]);
+        self.pos += read as u64;
+        Ok(read)
+    }
+}
+
+impl Write for Handle {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut state = lock(&self.state);
+        state.fail(FaultOp::Write, &self.path)?;
+        // Adding up every file is slow, so only done when there is a limit
+        let used = match state.capacity {
+            Some(_) => state.used(),
+            None => 0,
+        };
+        let mut data = lock(&self.data);
+
+        if self.append {
+            self.pos = data.bytes.len() as u64;
+        }
+        let start = self.pos as usize;
+        let end = start + buf.len();
+        let growth = end.saturating_sub(data.bytes.len()) as u64;
+        if state
+            .capacity
+            .is_some_and(|capacity| used + growth > capacity)
+        {
+            return Err(io::ErrorKind::StorageFull.into());
+        }
+
+        if data.bytes.len() < end {
+            data.bytes.resize(end, 0);
+        }
+        data.bytes[start..end].copy_from_slice(buf);
+        data.modified = SystemTime::now();
+        self.pos = end as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for Handle {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let len = lock(&self.data).bytes.len() as i64;
+        let pos = match pos {
+            SeekFrom::Start(offset) => offset as i64,
+            SeekFrom::End(offset) => len + offset,
+            SeekFrom::Current(offset) => self.pos as i64 + offset,
+        };
+        if pos < 0 {
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+        self.pos = pos as u64;
+        Ok(self.pos)
+    }
+}
+
+impl ReadHandle for Handle {
+    fn metadata(&self) -> io::Result<FileMeta> {
+        Ok(meta_of(&lock(&self.data)))
+    }
+}
+
+impl WriteHandle for Handle {
+    fn set_len(&self, len: u64) -> io::Result<()> {
+        let mut state = lock(&self.state);
+        state.fail(FaultOp::Write, &self.path)?;
+        lock(&self.data).bytes.resize(len as usize, 0);
+        Ok(())
+    }
+
+    fn set_modified(&self, time: SystemTime) -> io::Result<()> {
+        lock(&self.data).modified = time;
+        Ok(())
+    }
+
+    fn sync_data(&self) -> io::Result<()> {
+        lock(&self.state).fail(FaultOp::Sync, &self.path)
+    }
+
+    fn sync_all(&self) -> io::Result<()> {
+        self.sync_data()
+    }
+}
+
+fn meta_of(data: &FileData) -> FileMeta {
+    FileMeta {
+        len: data.bytes.len() as u64,
+        is_dir: false,
+        modified: Some(data.modified),
+        created: Some(data.modified),
+        device: None,
+        inode: None,
+        raw: None,
+    }
+}
+
+// A panic while holding a lock leaves the data as consistent as any write
+// could, so poisoning is ignored
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
@@ -0,0 +1,184 @@
+mod memory;
+mod real;
+mod walk;
+
+pub use memory::{Fault, FaultOp, MemoryFs};
+pub use real::RealFs;
+
+use std::fs::{File, Metadata};
+use std::io::{self, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+// Everything the engine does to files goes through this, so runs can be played
+// against `MemoryFs` with faults injected where the disk would fail
+pub trait FileSystem: Send + Sync {
+    fn metadata(&self, path: &Path) -> io::Result<FileMeta>;
+
+    fn exists(&self, path: &Path) -> bool {
+        self.metadata(path).is_ok()
+    }
+
+    // Every file below `root` with its metadata as the walk reaches it, leaving
+    // out what can't be read. The order is stable, by name within each folder,
+    // however many `threads` read folders.
+    fn walk_files(
+        &self,
+        root: &Path,
+        threads: usize,
+    ) -> Box<dyn Iterator<Item = (PathBuf, FileMeta)> + Send + '_>;
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>>;
+
+    fn create_dir(&self, path: &Path) -> io::Result<()>;
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()>;
+
+    // Removes an empty folder
+    fn remove_dir(&self, path: &Path) -> io::Result<()>;
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn ReadHandle>>;
+
+    // Creates the file, or empties it if it exists
+    fn create(&self, path: &Path) -> io::Result<Box<dyn WriteHandle>>;
+
+    // Opens an existing file for writing without truncating it
+    fn open_write(&self, path: &Path) -> io::Result<Box<dyn WriteHandle>>;
+
+    // Opens an existing file so that every write lands at its end
+    fn open_append(&self, path: &Path) -> io::Result<Box<dyn WriteHandle>>;
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        let mut content = String::new();
+        self.open(path)?.read_to_string(&mut content)?;
+        Ok(content)
+    }
+}
+
+pub trait ReadHandle: Read + Seek + Send {
+    fn metadata(&self) -> io::Result<FileMeta>;
+
+    // The file on disk, for copying in the kernel
+    fn as_file(&self) -> Option<&File> {
+        None
+    }
+}
+
+// Can read back what is in the file as well, the index keeps its database
+// behind one
+pub trait WriteHandle: Read + Write + Seek + Send {
+    fn set_len(&self, len: u64) -> io::Result<()>;
+
+    fn set_modified(&self, time: SystemTime) -> io::Result<()>;
+
+    fn sync_data(&self) -> io::Result<()>;
+
+    fn sync_all(&self) -> io::Result<()>;
+
+    // The file on disk, for copying permissions, ownership and the like
+    fn as_file(&self) -> Option<&File> {
+        None
+    }
+}
+
+// What the engine needs to know about a file. Mirrors the parts of
+// `std::fs::Metadata` it uses, which only exist for files on disk.
+#[derive(Debug, Clone)]
+pub struct FileMeta {
+    len: u64,
+    is_dir: bool,
+    modified: Option<SystemTime>,
+    created: Option<SystemTime>,
+    // Identifies the disk or share the file lives on
+    device: Option<u64>,
+    // Tells a file apart from one that replaced it under the same name
+    inode: Option<u64>,
+    raw: Option<Metadata>,
+}
+
+impl FileMeta {
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.is_dir
+    }
+
+    pub fn is_file(&self) -> bool {
+        !self.is_dir
+    }
+
+    pub fn modified(&self) -> io::Result<SystemTime> {
+        self.modified
+            .ok_or_else(|| unsupported("modification time"))
+    }
+
+    pub fn created(&self) -> io::Result<SystemTime> {
+        self.created.ok_or_else(|| unsupported("creation time"))
+    }
+
+    pub fn device(&self) -> Option<u64> {
+        self.device
+    }
+
+    pub fn inode(&self) -> Option<u64> {
+        self.inode
+    }
+
+    // The metadata of a file on disk
+    pub fn raw(&self) -> Option<&Metadata> {
+        self.raw.as_ref()
+    }
+}
+
+impl From<Metadata> for FileMeta {
+    fn from(meta: Metadata) -> Self {
+        Self {
+            len: meta.len(),
+            is_dir: meta.is_dir(),
+            modified: meta.modified().ok(),
+            created: meta.created().ok(),
+            device: device_of(&meta),
+            inode: inode_of(&meta),
+            raw: Some(meta),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn device_of(meta: &Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(meta.dev())
+}
+
+#[cfg(not(unix))]
+fn device_of(_meta: &Metadata) -> Option<u64> {
+    None
+}
+
+#[cfg(unix)]
+fn inode_of(meta: &Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(meta.ino())
+}
+
+#[cfg(not(unix))]
+fn inode_of(_meta: &Metadata) -> Option<u64> {
+    None
+}
+
+fn unsupported(what: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("{} is not available on this file system", what),
+    )
+}
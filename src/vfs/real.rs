@@ -0,0 +1,110 @@
+use super::walk::ParallelWalk;
+use super::{FileMeta, FileSystem, ReadHandle, WriteHandle};
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+// The file system on disk
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RealFs;
+
+impl FileSystem for RealFs {
+    fn metadata(&self, path: &Path) -> io::Result<FileMeta> {
+        fs::metadata(path).map(FileMeta::from)
+    }
+
+    fn walk_files(
+        &self,
+        root: &Path,
+        threads: usize,
+    ) -> Box<dyn Iterator<Item = (PathBuf, FileMeta)> + Send + '_> {
+        Box::new(ParallelWalk::new(root, threads))
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        Ok(fs::read_dir(path)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .collect())
+    }
+
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir(path)
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir_all(path)
+    }
+
+    fn remove_dir(&self, path: &Path) -> io::Result<()> {
+        fs::remove_dir(path)
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn ReadHandle>> {
+        Ok(Box::new(File::open(path)?))
+    }
+
+    fn create(&self, path: &Path) -> io::Result<Box<dyn WriteHandle>> {
+        Ok(Box::new(
+            OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(path)?,
+        ))
+    }
+
+    fn open_write(&self, path: &Path) -> io::Result<Box<dyn WriteHandle>> {
+        Ok(Box::new(OpenOptions::new().read(true).write(true).open(path)?))
+    }
+
+    fn open_append(&self, path: &Path) -> io::Result<Box<dyn WriteHandle>> {
+        Ok(Box::new(OpenOptions::new().read(true).append(true).open(path)?))
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(from, to)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+}
+
+impl ReadHandle for File {
+    fn metadata(&self) -> io::Result<FileMeta> {
+        File::metadata(self).map(FileMeta::from)
+    }
+
+    fn as_file(&self) -> Option<&File> {
+        Some(self)
+    }
+}
+
+impl WriteHandle for File {
+    fn set_len(&self, len: u64) -> io::Result<()> {
+        File::set_len(self, len)
+    }
+
+    fn set_modified(&self, time: SystemTime) -> io::Result<()> {
+        File::set_modified(self, time)
+    }
+
+    fn sync_data(&self) -> io::Result<()> {
+        File::sync_data(self)
+    }
+
+    fn sync_all(&self) -> io::Result<()> {
+        File::sync_all(self)
+    }
+
+    fn as_file(&self) -> Option<&File> {
+        Some(self)
+    }
+}
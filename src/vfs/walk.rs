@@ -0,0 +1,188 @@
+use super::FileMeta;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::vec;
+
+// Folders read ahead of the walk. Readers wait once this many are waiting to
+// be walked, unless it's the folder the walk is stuck on.
+const READ_AHEAD: usize = 256;
+
+// Walks a tree with several threads reading folders at once. Files come out
+// in the same order as a walk on one thread sorting each folder by name, so
+// plans made from it can be reproduced.
+pub struct ParallelWalk {
+    shared: Arc<Shared>,
+    // The rest of every folder being walked, innermost last
+    stack: Vec<vec::IntoIter<Entry>>,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    // Notified whenever a folder is read, taken or asked for, and on drop
+    changed: Condvar,
+}
+
+struct State {
+    // Folders to read. Paths sort in the order the walk reaches them, so the
+    // first is the one needed soonest.
+    pending: BTreeSet<PathBuf>,
+    reading: usize,
+    read: HashMap<PathBuf, Vec<Entry>>,
+    // The folder the walk is waiting for
+    wanted: Option<PathBuf>,
+    // Set when the walk is dropped
+    stopped: bool,
+}
+
+struct Entry {
+    path: PathBuf,
+    // None for folders. For files it comes with the folder listing, so they
+    // aren't looked up a second time.
+    meta: Option<FileMeta>,
+}
+
+impl ParallelWalk {
+    pub fn new(root: &Path, threads: usize) -> Self {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                pending: BTreeSet::from([root.to_path_buf()]),
+                reading: 0,
+                read: HashMap::new(),
+                wanted: None,
+                stopped: false,
+            }),
+            changed: Condvar::new(),
+        });
+        for _ in 0..threads.max(1) {
+            let shared = Arc::clone(&shared);
+            thread::spawn(move || shared.read_folders());
+        }
+
+        Self {
+            shared,
+            stack: vec![
+                vec![Entry {
+                    path: root.to_path_buf(),
+                    meta: None,
+                }]
+                .into_iter(),
+            ],
+        }
+    }
+}
+
+impl Iterator for ParallelWalk {
+    type Item = (PathBuf, FileMeta);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let Some(entry) = self.stack.last_mut()?.next() else {
+                self.stack.pop();
+                continue;
+            };
+            match entry.meta {
+                Some(meta) => return Some((entry.path, meta)),
+                None => {
+                    let entries = self.shared.take(&entry.path);
+                    self.stack.push(entries.into_iter());
+                }
+            }
+        }
+    }
+}
+
+impl Drop for ParallelWalk {
+    fn drop(&mut self) {
+        self.shared.lock().stopped = true;
+        self.shared.changed.notify_all();
+    }
+}
+
+impl Shared {
+    fn read_folders(&self) {
+        while let Some(folder) = self.next_folder() {
+            let entries = read_folder(&folder);
+            let mut state = self.lock();
+            state.reading -= 1;
+            state.pending.extend(
+                entries
+                    .iter()
+                    .filter(|entry| entry.meta.is_none())
+                    .map(|entry| entry.path.clone()),
+            );
+            state.read.insert(folder, entries);
+            drop(state);
+            self.changed.notify_all();
+        }
+    }
+
+    // None once the walk is dropped or the whole tree has been read
+    fn next_folder(&self) -> Option<PathBuf> {
+        let mut state = self.lock();
+        loop {
+            if state.stopped || (state.pending.is_empty() && state.reading == 0) {
+                return None;
+            }
+            let ready = state.pending.first().is_some_and(|first| {
+                state.read.len() < READ_AHEAD || state.wanted.as_ref() == Some(first)
+            });
+            if ready {
+                state.reading += 1;
+                return state.pending.pop_first();
+            }
+            state = self.changed.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    fn take(&self, folder: &Path) -> Vec<Entry> {
+        let mut state = self.lock();
+        loop {
+            if let Some(entries) = state.read.remove(folder) {
+                state.wanted = None;
+                drop(state);
+                // A reader may be waiting for room
+                self.changed.notify_all();
+                return entries;
+            }
+            if state.wanted.as_deref() != Some(folder) {
+                state.wanted = Some(folder.to_path_buf());
+                self.changed.notify_all();
+            }
+            state = self.changed.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+// Files and folders directly in `folder` sorted by name, leaving out what can't
+// be read along with links, which aren't followed
+fn read_folder(folder: &Path) -> Vec<Entry> {
+    let Ok(listing) = fs::read_dir(folder) else {
+        return Vec::new();
+    };
+    let mut entries: Vec<_> = listing
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let file_type = entry.file_type().ok()?;
+            let meta = if file_type.is_dir() {
+                None
+            } else if file_type.is_file() {
+                Some(entry.metadata().ok()?.into())
+            } else {
+                return None;
+            };
+            Some(Entry {
+                path: entry.path(),
+                meta,
+            })
+        })
+        .collect();
+    entries.sort_by(|a, b| a.path.file_name().cmp(&b.path.file_name()));
+    entries
+}
@@ -0,0 +1,540 @@
+// Whole runs played against `MemoryFs`, with faults injected where a disk
+// would fail
+
+use file_organizer::OrganizeError;
+use file_organizer::models::{
+    CheckpointOptions, ConcurrencyOptions, ConflictPolicy, CopyOptions, ErrorPolicy, InFlightFile,
+    Mode, RetryOptions, SaveState,
+};
+use file_organizer::organizer::{Event, Organizer, get_all_files, process_files, temp_path_for};
+use file_organizer::runs::{journal_path, read_run, undo::undo_operations};
+use file_organizer::vfs::{Fault, FaultOp, FileSystem, MemoryFs};
+use std::collections::HashSet;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+// Files are dated 2024-01-01 by `MemoryFs`
+const PICTURES: &str = "/out/Picture/2024-01-01";
+
+// One file at a time, so which file hits a fault doesn't depend on timing
+fn options() -> CopyOptions {
+    CopyOptions {
+        concurrency: ConcurrencyOptions {
+            jobs: 1,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+fn organizer(fs: &MemoryFs, options: CopyOptions) -> Organizer {
+    Organizer::new("/in", "/out")
+        .options(options)
+        .file_system(Arc::new(fs.clone()))
+}
+
+fn picture(name: &str) -> PathBuf {
+    Path::new(PICTURES).join(name)
+}
+
+// Bytes that differ from one position to the next, so a copy from the wrong
+// offset shows
+fn pattern(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i % 251) as u8).collect()
+}
+
+fn partial_files(fs: &MemoryFs) -> Vec<PathBuf> {
+    fs.files()
+        .into_iter()
+        .filter(|path| path.to_string_lossy().ends_with(".forg-partial"))
+        .collect()
+}
+
+#[test]
+fn organizes_files_by_type_and_date() {
+    let fs = MemoryFs::new();
+    fs.add_file("/in/a.jpg", "a");
+    fs.add_file("/in/nested/b.jpg", "b");
+
+    let report = organizer(&fs, options()).run(|_: &Event| {}).unwrap();
+
+    assert_eq!(report.copied, 2);
+    assert_eq!(fs.contents(picture("a.jpg")).unwrap(), b"a");
+    assert_eq!(fs.contents(picture("b.jpg")).unwrap(), b"b");
+    assert_eq!(fs.contents("/in/a.jpg").unwrap(), b"a");
+}
+
+#[test]
+fn disk_full_fails_the_file_and_leaves_no_partial() {
+    let fs = MemoryFs::new();
+    fs.add_file("/in/a.jpg", vec![1; 1000]);
+    fs.add_file("/in/b.jpg", vec![2; 1000]);
+    // Room for the sources and one copy
+    fs.set_capacity(Some(3500));
+
+    let report = organizer(
+        &fs,
+        CopyOptions {
+            on_error: ErrorPolicy::Skip,
+            ..options()
+        },
+    )
+    .run(|_: &Event| {})
+    .unwrap();
+
+    assert_eq!(report.copied, 1);
+    assert_eq!(report.failures.len(), 1);
+    assert_eq!(report.failures[0].path, Path::new("/in/b.jpg"));
+    assert_eq!(report.failures[0].kind, io::ErrorKind::StorageFull);
+    assert!(fs.contents(picture("b.jpg")).is_none());
+    assert!(partial_files(&fs).is_empty());
+    assert!(report.failed.is_some());
+}
+
+#[test]
+fn permission_denied_aborts_and_keeps_what_was_copied_undoable() {
+    let fs = MemoryFs::new();
+    fs.add_file("/in/a.jpg", "a");
+    fs.add_file("/in/b.jpg", "b");
+    fs.inject(Fault::new(FaultOp::Open, io::ErrorKind::PermissionDenied).on("/in/b.jpg"));
+
+    let result = organizer(&fs, options())
+        .log_run("permission_denied")
+        .run(|_: &Event| {});
+
+    match result {
+        Err(OrganizeError::File { source, error, .. }) => {
+            assert_eq!(source, Path::new("/in/b.jpg"));
+            assert_eq!(error.kind(), io::ErrorKind::PermissionDenied);
+        }
+        other => panic!("expected the file error, got {:?}", other.map(|r| r.copied)),
+    }
+    assert_eq!(fs.contents(picture("a.jpg")).unwrap(), b"a");
+
+    let operations = read_run(&fs, &journal_path("permission_denied")).unwrap();
+    let undone = undo_operations(&fs, &operations);
+    assert!(undone.skipped.is_empty());
+    assert!(!fs.exists(Path::new("/out")));
+}
+
+#[test]
+fn moves_are_logged_and_undone() {
+    // The second time the rename fails and the file is copied instead, after
+    // its move was already logged
+    for rename_fails in [false, true] {
+        let fs = MemoryFs::new();
+        fs.add_file("/in/a.jpg", "a");
+        if rename_fails {
+            fs.inject(
+                Fault::new(FaultOp::Rename, io::ErrorKind::CrossesDevices)
+                    .on("/in/a.jpg")
+                    .times(1),
+            );
+        }
+
+        let report = organizer(&fs, options())
+            .mode(Mode::Move)
+            .log_run("move")
+            .run(|_: &Event| {})
+            .unwrap();
+
+        assert_eq!(report.copied, 1);
+        assert!(!fs.exists(Path::new("/in/a.jpg")));
+        assert_eq!(fs.contents(picture("a.jpg")).unwrap(), b"a");
+
+        let operations = read_run(&fs, &journal_path("move")).unwrap();
+        let undone = undo_operations(&fs, &operations);
+        assert!(undone.skipped.is_empty(), "{}", rename_fails);
+        assert_eq!(fs.contents("/in/a.jpg").unwrap(), b"a");
+        assert!(!fs.exists(Path::new("/out")));
+    }
+}
+
+#[test]
+fn name_conflicts_follow_the_policy() {
+    for (policy, expected) in [
+        (ConflictPolicy::Overwrite, &[("a.jpg", "new")][..]),
+        (ConflictPolicy::Skip, &[("a.jpg", "old")][..]),
+        (
+            ConflictPolicy::Rename,
+            &[("a.jpg", "old"), ("a (1).jpg", "new")][..],
+        ),
+    ] {
+        let fs = MemoryFs::new();
+        fs.add_file("/in/a.jpg", "new");
+        fs.add_file(picture("a.jpg"), "old");
+
+        let mut conflicts = 0;
+        organizer(&fs, options())
+            .conflict_policy(policy)
+            .run(|event: &Event| {
+                if let Event::Conflict { .. } = event {
+                    conflicts += 1;
+                }
+            })
+            .unwrap();
+
+        assert_eq!(conflicts, 1, "{:?}", policy);
+        let outputs: Vec<_> = fs
+            .files()
+            .into_iter()
+            .filter(|path| path.starts_with("/out"))
+            .collect();
+        assert_eq!(outputs.len(), expected.len(), "{:?}", policy);
+        for (name, contents) in expected {
+            assert_eq!(
+                fs.contents(picture(name)).unwrap(),
+                contents.as_bytes(),
+                "{:?}",
+                policy
+            );
+        }
+    }
+}
+
+#[test]
+fn files_for_one_target_are_copied_one_after_another() {
+    let fs = MemoryFs::new();
+    let sources: Vec<_> = (0..8u8)
+        .map(|n| {
+            let contents = vec![n; 1024 * 1024];
+            fs.add_file(format!("/in/{}/a.jpg", n), contents.clone());
+            contents
+        })
+        .collect();
+
+    let report = organizer(
+        &fs,
+        CopyOptions {
+            concurrency: ConcurrencyOptions {
+                jobs: 4,
+                ..Default::default()
+            },
+            ..options()
+        },
+    )
+    .conflict_policy(ConflictPolicy::Overwrite)
+    .run(|_: &Event| {})
+    .unwrap();
+
+    assert_eq!(report.copied, 8);
+    // The last file planned is the last one written
+    assert_eq!(fs.contents(picture("a.jpg")).unwrap(), sources[7]);
+    assert!(partial_files(&fs).is_empty());
+}
+
+#[test]
+fn workers_stay_close_behind_a_slow_file() {
+    let fs = MemoryFs::new();
+    fs.add_file("/in/0/a.jpg", "slow");
+    for n in 0..3000 {
+        fs.add_file(format!("/in/1/{:04}.jpg", n), "fast");
+    }
+    // Holds the first file up while the others are copied
+    fs.inject(
+        Fault::new(FaultOp::Read, io::ErrorKind::TimedOut)
+            .on("/in/0/a.jpg")
+            .times(1),
+    );
+
+    let (mut finished, mut furthest_ahead) = (0, 0);
+    let report = organizer(
+        &fs,
+        CopyOptions {
+            concurrency: ConcurrencyOptions {
+                jobs: 4,
+                ..Default::default()
+            },
+            retry: RetryOptions {
+                limit: 1,
+                base_delay: Duration::from_millis(500),
+            },
+            ..options()
+        },
+    )
+    .run(|event: &Event| match event {
+        Event::FileStarted { index, .. } => furthest_ahead = furthest_ahead.max(index - finished),
+        Event::FileFinished { .. } => finished += 1,
+        _ => {}
+    })
+    .unwrap();
+
+    assert_eq!(report.copied, 3001);
+    // Files that end before the slow one wait to be reported after it, and
+    // only so many are let through
+    assert!(furthest_ahead <= 1024, "{}", furthest_ahead);
+}
+
+#[test]
+fn transient_errors_are_retried() {
+    let fs = MemoryFs::new();
+    fs.add_file("/in/a.jpg", "a");
+    fs.inject(
+        Fault::new(FaultOp::Read, io::ErrorKind::TimedOut)
+            .on("/in/a.jpg")
+            .times(1),
+    );
+
+    let report = organizer(
+        &fs,
+        CopyOptions {
+            retry: RetryOptions {
+                limit: 3,
+                base_delay: Duration::from_millis(1),
+            },
+            ..options()
+        },
+    )
+    .run(|_: &Event| {})
+    .unwrap();
+
+    assert_eq!(report.copied, 1);
+    assert_eq!(report.retries, 1);
+    assert_eq!(report.warnings[0].message, "Copied after 1 retry");
+    assert_eq!(fs.contents(picture("a.jpg")).unwrap(), b"a");
+}
+
+#[test]
+fn a_retried_write_is_hashed_once() {
+    let fs = MemoryFs::new();
+    let big = pattern(2 * 1024 * 1024);
+    fs.add_file("/in/big.jpg", big.clone());
+    fs.inject(
+        Fault::new(FaultOp::Write, io::ErrorKind::TimedOut)
+            .on(temp_path_for(&picture("big.jpg")))
+            .after(1)
+            .times(1),
+    );
+
+    let mut hash = None;
+    let report = organizer(
+        &fs,
+        CopyOptions {
+            hash: true,
+            retry: RetryOptions {
+                limit: 3,
+                base_delay: Duration::from_millis(1),
+            },
+            ..options()
+        },
+    )
+    .run(|event: &Event| {
+        if let Event::FileVerified { hash: Some(h), .. } = event {
+            hash = Some(h.to_string());
+        }
+    })
+    .unwrap();
+
+    assert_eq!(report.retries, 1);
+    assert_eq!(fs.contents(picture("big.jpg")).unwrap(), big);
+    assert_eq!(hash.unwrap(), blake3::hash(&big).to_hex().to_string());
+}
+
+#[test]
+fn interrupted_copy_resumes_from_its_offset() {
+    let fs = MemoryFs::new();
+    let big = pattern(4 * 1024 * 1024);
+    fs.add_file("/in/a.jpg", "a");
+    fs.add_file("/in/big.jpg", big.clone());
+    // Fails the second read of the big file, and the run is stopped while it
+    // waits to retry
+    fs.inject(
+        Fault::new(FaultOp::Read, io::ErrorKind::TimedOut)
+            .on("/in/big.jpg")
+            .after(1)
+            .times(1),
+    );
+    let options = CopyOptions {
+        hash: true,
+        retry: RetryOptions {
+            limit: 1,
+            base_delay: Duration::from_secs(60),
+        },
+        ..options()
+    };
+
+    let organizer = organizer(&fs, options.clone()).save_to("/saves/run.forg");
+    let stop = organizer.stop_signal();
+    let report = organizer
+        .run(|event: &Event| {
+            if let Event::FileRetry { .. } = event {
+                stop.store(true, Ordering::SeqCst);
+            }
+        })
+        .unwrap();
+
+    let save_state = report.interrupted.expect("the run was stopped");
+    assert_eq!(save_state.processed_files.len(), 1);
+    let partial = &save_state.in_flight[0];
+    assert_eq!(partial.target, picture("big.jpg"));
+    assert!(partial.offset > 0 && partial.offset < big.len() as u64);
+    assert_eq!(partial_files(&fs), [temp_path_for(&picture("big.jpg"))]);
+
+    let offset = partial.offset;
+    let processed: HashSet<_> = save_state
+        .processed_files
+        .iter()
+        .map(|file| file.path.clone())
+        .collect();
+    let files = get_all_files(&fs, Path::new("/in"), 1)
+        .into_iter()
+        .filter(|file| !processed.contains(&file.path));
+
+    let mut first_progress = None;
+    let mut hash = None;
+    let report = process_files(
+        &fs,
+        files,
+        save_state,
+        Some(PathBuf::from("/saves/run.forg")),
+        None,
+        None,
+        &options,
+        &mut |event: &Event| match event {
+            Event::FileProgress { copied, .. } if first_progress.is_none() => {
+                first_progress = Some(*copied);
+            }
+            Event::FileVerified { hash: Some(h), .. } => hash = Some(h.to_string()),
+            _ => {}
+        },
+        Arc::new(AtomicBool::new(false)),
+    )
+    .unwrap();
+
+    assert_eq!(report.copied, 1);
+    assert!(report.interrupted.is_none());
+    assert_eq!(first_progress, Some(offset));
+    assert_eq!(fs.contents(picture("big.jpg")).unwrap(), big);
+    assert_eq!(hash.unwrap(), blake3::hash(&big).to_hex().to_string());
+    assert!(partial_files(&fs).is_empty());
+}
+
+#[test]
+fn the_save_lists_a_partial_file_while_it_is_copied() {
+    let fs = MemoryFs::new();
+    let big = pattern(8 * 1024 * 1024);
+    fs.add_file("/in/big.jpg", big.clone());
+    let save_path = Path::new("/saves/run.forg");
+    fs.create_dir_all(Path::new("/saves")).unwrap();
+
+    // What a crash at each step of the copy would leave to resume from
+    let mut resumable = Vec::new();
+    organizer(
+        &fs,
+        CopyOptions {
+            checkpoint: CheckpointOptions {
+                every_files: 100,
+                interval: Duration::ZERO,
+            },
+            ..options()
+        },
+    )
+    .save_to(save_path)
+    .run(|event: &Event| {
+        if let Event::FileProgress { .. } = event
+            && let Ok(save_state) = SaveState::load(&fs, save_path)
+            && let [partial] = &save_state.in_flight[..]
+            // Gone once the copy was moved into place
+            && let Some(contents) = fs.contents(temp_path_for(&partial.target))
+        {
+            resumable.push((partial.offset, contents.len() as u64));
+        }
+    })
+    .unwrap();
+
+    assert!(!resumable.is_empty());
+    for (offset, on_disk) in resumable {
+        assert!(offset > 0 && offset < big.len() as u64);
+        assert!(on_disk >= offset);
+    }
+}
+
+#[test]
+fn a_partial_file_that_went_bad_is_copied_again() {
+    let fs = MemoryFs::new();
+    let big = pattern(4 * 1024 * 1024);
+    fs.add_file("/in/big.jpg", big.clone());
+
+    // A partial file whose early bytes went bad, too far back for the check of
+    // its last stretch to see. Only comparing the hashes finds it.
+    let offset = 3 * 1024 * 1024;
+    let mut partial = big[..offset].to_vec();
+    partial[0] ^= 0xff;
+    fs.add_file(temp_path_for(&picture("big.jpg")), partial);
+
+    let mut save_state = SaveState::new("/in".into(), "/out".into(), Default::default());
+    save_state.in_flight.push(InFlightFile {
+        source: "/in/big.jpg".into(),
+        target: picture("big.jpg"),
+        size: big.len() as u64,
+        modified: fs
+            .metadata(Path::new("/in/big.jpg"))
+            .unwrap()
+            .modified()
+            .unwrap(),
+        offset: offset as u64,
+    });
+
+    let (mut first_progress, mut hash) = (None, None);
+    process_files(
+        &fs,
+        get_all_files(&fs, Path::new("/in"), 1).into_iter(),
+        save_state,
+        None,
+        None,
+        None,
+        &CopyOptions {
+            hash: true,
+            ..options()
+        },
+        &mut |event: &Event| match event {
+            Event::FileProgress { copied, .. } if first_progress.is_none() => {
+                first_progress = Some(*copied);
+            }
+            Event::FileVerified { hash: Some(h), .. } => hash = Some(h.to_string()),
+            _ => {}
+        },
+        Arc::new(AtomicBool::new(false)),
+    )
+    .unwrap();
+
+    assert_eq!(first_progress, Some(0));
+    assert_eq!(fs.contents(picture("big.jpg")).unwrap(), big);
+    assert_eq!(hash.unwrap(), blake3::hash(&big).to_hex().to_string());
+}
+
+#[test]
+fn runs_with_a_log_and_an_index_stay_on_the_file_system() {
+    let fs = MemoryFs::new();
+    fs.add_file("/in/a.jpg", "a");
+    let index = Path::new("/data/index.redb");
+
+    let run = || {
+        organizer(&fs, options())
+            .log_run("memory")
+            .index(index)
+            .run(|_: &Event| {})
+            .unwrap()
+    };
+    assert_eq!(run().copied, 1);
+    let again = run();
+    assert_eq!(again.copied, 0);
+    assert_eq!(again.unchanged, 1);
+
+    assert!(fs.exists(index));
+    assert!(fs.exists(&journal_path("memory")));
+}
+
+#[test]
+fn a_fault_for_zero_times_never_fires() {
+    let fs = MemoryFs::new();
+    fs.add_file("/in/a.jpg", "a");
+    fs.inject(Fault::new(FaultOp::Open, io::ErrorKind::PermissionDenied).times(0));
+
+    let report = organizer(&fs, options()).run(|_: &Event| {}).unwrap();
+
+    assert_eq!(report.copied, 1);
+}
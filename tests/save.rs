@@ -0,0 +1,182 @@
+// Save files as earlier versions wrote them, decoded by this one
+
+use file_organizer::models::{ConflictPolicy, SaveState, Strategy};
+use file_organizer::save::format::{CURRENT_VERSION, SaveFormatError, decode};
+use file_organizer::vfs::{FileSystem, MemoryFs};
+use std::path::Path;
+use std::time::{Duration, UNIX_EPOCH};
+
+// 2024-01-01, as `SystemTime` is serialized
+const MODIFIED: &str = r#"{"secs_since_epoch":1704067200,"nanos_since_epoch":0}"#;
+
+fn fixture(template: &str) -> String {
+    template.replace("MODIFIED", MODIFIED)
+}
+
+#[test]
+fn reads_a_bare_version_1_save() {
+    // Version 1 stored the date folder of the first copied file as the output
+    let content = fixture(
+        r#"{
+            "input_path": "/in",
+            "output_path": "/out/Picture/2024-01-01",
+            "processed_files": [
+                {"path": "/in/a.jpg", "name": "a.jpg", "size": 1, "modified": MODIFIED}
+            ]
+        }"#,
+    );
+
+    let decoded = decode(&content).unwrap();
+    assert_eq!(decoded.version, 1);
+    assert!(!decoded.appendable);
+    assert_eq!(decoded.state.output_path, Path::new("/out"));
+    assert_eq!(decoded.state.processed_files.len(), 1);
+    let file = &decoded.state.processed_files[0];
+    assert_eq!(file.path, Path::new("/in/a.jpg"));
+    assert_eq!(file.modified, UNIX_EPOCH + Duration::from_secs(1704067200));
+    assert_eq!(file.target, None);
+    assert!(decoded.state.in_flight.is_empty());
+}
+
+#[test]
+fn reads_a_version_2_envelope_with_one_file_in_flight() {
+    // With settings recorded the output is the real root, even one that looks
+    // like a date folder
+    let content = fixture(
+        r#"{
+            "format": "file-organizer-save",
+            "version": 2,
+            "state": {
+                "input_path": "/in",
+                "output_path": "/out/Picture/2024-01-01",
+                "processed_files": [],
+                "settings": {
+                    "strategy": "Type",
+                    "filters": {"extensions": [], "min_size": null, "max_size": null},
+                    "conflict_policy": "Skip",
+                    "mode": "Copy"
+                },
+                "in_flight": {
+                    "source": "/in/big.mov",
+                    "target": "/out/Video/big.mov",
+                    "size": 100,
+                    "modified": MODIFIED,
+                    "offset": 40
+                }
+            }
+        }"#,
+    );
+
+    let decoded = decode(&content).unwrap();
+    assert_eq!(decoded.version, 2);
+    assert!(!decoded.appendable);
+    let state = decoded.state;
+    assert_eq!(state.output_path, Path::new("/out/Picture/2024-01-01"));
+    assert_eq!(state.settings.strategy, Strategy::Type);
+    assert_eq!(state.settings.conflict_policy, ConflictPolicy::Skip);
+    assert_eq!(state.in_flight.len(), 1);
+    assert_eq!(state.in_flight[0].source, Path::new("/in/big.mov"));
+    assert_eq!(state.in_flight[0].offset, 40);
+}
+
+#[test]
+fn reads_a_version_3_journal() {
+    let content = fixture(concat!(
+        r#"{"format":"file-organizer-journal","version":3}"#,
+        "\n",
+        r#"["/in/a.jpg",1,1704067200,0,"/out/Picture/2024-01-01/a.jpg","abc"]"#,
+        "\n",
+        r#"{"state":{"input_path":"/in","output_path":"/out","in_flight":null}}"#,
+        "\n",
+        r#"["/in/b.jpg",2,1704067200,5,null,null]"#,
+        "\n",
+        r#"{"state":{"input_path":"/in","output_path":"/out","in_flight":{"source":"/in/c.mov","target":"/out/Video/2024-01-01/c.mov","size":100,"modified":MODIFIED,"offset":60}}}"#,
+        "\n",
+    ));
+
+    let decoded = decode(&content).unwrap();
+    assert_eq!(decoded.version, 3);
+    assert!(decoded.appendable);
+    let state = decoded.state;
+    assert_eq!(state.output_path, Path::new("/out"));
+
+    let files = &state.processed_files;
+    assert_eq!(files.len(), 2);
+    assert_eq!(files[0].name, "a.jpg");
+    assert_eq!(
+        files[0].target.as_deref(),
+        Some(Path::new("/out/Picture/2024-01-01/a.jpg"))
+    );
+    assert_eq!(files[0].hash.as_deref(), Some("abc"));
+    assert_eq!(files[1].modified, UNIX_EPOCH + Duration::new(1704067200, 5));
+    assert_eq!(files[1].target, None);
+
+    // The last state record wins
+    assert_eq!(state.in_flight.len(), 1);
+    assert_eq!(state.in_flight[0].offset, 60);
+}
+
+#[test]
+fn refuses_saves_from_a_newer_version() {
+    let newer = CURRENT_VERSION + 1;
+    let json = format!(
+        r#"{{"format":"file-organizer-save","version":{},"state":{{"input_path":"/in","output_path":"/out"}}}}"#,
+        newer
+    );
+    let journal = format!(
+        "{{\"format\":\"file-organizer-journal\",\"version\":{}}}\n{}\n",
+        newer, r#"{"state":{"input_path":"/in","output_path":"/out"}}"#
+    );
+
+    for content in [json, journal] {
+        match decode(&content) {
+            Err(SaveFormatError::UnsupportedVersion(version)) => assert_eq!(version, newer),
+            Err(e) => panic!("expected an unsupported version, got: {}", e),
+            Ok(_) => panic!("a save from version {} was read", newer),
+        }
+    }
+}
+
+#[test]
+fn a_journal_cut_off_mid_line_is_rewritten_before_it_grows() {
+    // A crash while appending the second entry
+    let content = concat!(
+        r#"{"format":"file-organizer-journal","version":4}"#,
+        "\n",
+        r#"["/in/a.jpg",1,1704067200,0,"/out/Picture/2024-01-01/a.jpg",null]"#,
+        "\n",
+        r#"{"state":{"input_path":"/in","output_path":"/out","in_flight":[]}}"#,
+        "\n",
+        r#"["/in/b.jpg",2,17040"#,
+    );
+
+    let decoded = decode(content).unwrap();
+    assert!(!decoded.appendable);
+    assert_eq!(decoded.state.processed_files.len(), 1);
+
+    let fs = MemoryFs::new();
+    fs.create_dir_all(Path::new("/saves")).unwrap();
+    let save_path = Path::new("/saves/run.forg");
+    fs.add_file(save_path, content);
+
+    let mut state = SaveState::load_versioned(&fs, save_path).unwrap().state;
+    let mut added = state.processed_files[0].clone();
+    added.path = "/in/b.jpg".into();
+    added.name = "b.jpg".to_string();
+    state.add_processed_file(added);
+    state.save_to(&fs, save_path).unwrap();
+
+    // Written whole again rather than appended after the broken line, which
+    // would leave a line in the middle that can't be read
+    let rewritten = String::from_utf8(fs.contents(save_path).unwrap()).unwrap();
+    let decoded = decode(&rewritten).unwrap();
+    assert_eq!(decoded.version, CURRENT_VERSION);
+    assert!(decoded.appendable);
+    let names: Vec<_> = decoded
+        .state
+        .processed_files
+        .iter()
+        .map(|file| file.name.as_str())
+        .collect();
+    assert_eq!(names, ["a.jpg", "b.jpg"]);
+}